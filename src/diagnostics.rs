@@ -0,0 +1,265 @@
+use std::{
+    fmt,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+use tree_sitter::Node;
+
+pub type DiagnosticsHandle = Arc<Mutex<Diagnostics>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Level {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Level::Error),
+            "warning" => Some(Level::Warning),
+            "note" => Some(Level::Note),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Error => write!(f, "error"),
+            Level::Warning => write!(f, "warning"),
+            Level::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// Reports problems in the input to the end user, pointing at where they are
+/// in the source.
+pub struct Diagnostics {
+    file: String,
+    source: String,
+    errors: usize,
+}
+
+impl Diagnostics {
+    pub fn new(file: &str, source: &str) -> Self {
+        Diagnostics {
+            file: file.to_owned(),
+            source: source.to_owned(),
+            errors: 0,
+        }
+    }
+
+    /// Prints a diagnostic for the bytes of the source in `span`.
+    pub fn report(&mut self, level: Level, span: Range<usize>, message: &str) {
+        if let Level::Error = level {
+            self.errors += 1;
+        }
+
+        eprintln!("{}", self.render(level, span, message));
+    }
+
+    /// Reports the `ERROR` and `MISSING` nodes tree-sitter produced while
+    /// parsing the input, returning how many there were.
+    pub fn report_syntax_errors(&mut self, node: Node, level: Level) -> usize {
+        if !node.has_error() {
+            return 0;
+        }
+
+        if node.is_missing() {
+            self.report(
+                level,
+                node.start_byte()..node.end_byte(),
+                format!("missing `{}`", node.kind()).as_str(),
+            );
+            return 1;
+        }
+
+        // everything inside of an error is part of it, so only the outermost
+        // one is reported
+        if node.is_error() {
+            self.report(level, node.start_byte()..node.end_byte(), "syntax error");
+            return 1;
+        }
+
+        let mut reported = 0;
+        for i in 0..node.child_count() {
+            if let Some(child) = node.child(i) {
+                reported += self.report_syntax_errors(child, level);
+            }
+        }
+
+        reported
+    }
+
+    /// How many errors have been reported. Compilation can't continue once
+    /// there are any.
+    pub fn error_count(&self) -> usize {
+        self.errors
+    }
+
+    /// Renders a diagnostic in the style of rustc, such as
+    ///
+    /// ```text
+    /// error: undefined variable `x`
+    ///  --> input.js:3:9
+    ///   |
+    /// 3 | let y = x + 1;
+    ///   |         ^
+    /// ```
+    fn render(&self, level: Level, span: Range<usize>, message: &str) -> String {
+        // spans come from the lowerer, so may not land on a character
+        let start = self.char_boundary(span.start);
+        let end = self.char_boundary(span.end.max(start));
+
+        let line_start = self.source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.source[start..]
+            .find('\n')
+            .map_or(self.source.len(), |i| start + i);
+
+        let line_number = self.source[..start].matches('\n').count() + 1;
+        let line = self.source[line_start..line_end].trim_end_matches('\r');
+        let before = &self.source[line_start..start];
+        let column = before.chars().count() + 1;
+
+        // spans over multiple lines are underlined to the end of the first
+        let underlined = &self.source[start..end.min(line_end)];
+        let carets = underlined.chars().count().max(1);
+
+        // keep tabs in the padding, so the carets line up with the source
+        let padding = before
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        let gutter = " ".repeat(line_number.to_string().len());
+
+        [
+            format!("{}: {}", level, message),
+            format!("{}--> {}:{}:{}", gutter, self.file, line_number, column),
+            format!("{} |", gutter),
+            format!("{} | {}", line_number, line),
+            format!("{} | {}{}", gutter, padding, "^".repeat(carets)),
+        ]
+        .join("\n")
+    }
+
+    /// The closest character boundary of the source at or before `offset`.
+    fn char_boundary(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, span: Range<usize>) -> String {
+        Diagnostics::new("input.js", source).render(Level::Error, span, "message")
+    }
+
+    fn expected(location: &str, line: &str, underline: &str) -> String {
+        let line_number = location.split(':').next().unwrap();
+        let gutter = " ".repeat(line_number.len());
+
+        format!(
+            "error: message\n{0}--> input.js:{1}\n{0} |\n{2} | {3}\n{0} | {4}",
+            gutter, location, line_number, line, underline
+        )
+    }
+
+    #[test]
+    fn render_first_line() {
+        let source = "let x = 1;\nlet y = x + 1;\n";
+        assert_eq!(render(source, 4..5), expected("1:5", "let x = 1;", "    ^"));
+        assert_eq!(render(source, 0..3), expected("1:1", "let x = 1;", "^^^"));
+    }
+
+    #[test]
+    fn render_last_line() {
+        let source = "a\nb\nlet y = x;";
+        assert_eq!(
+            render(source, 12..13),
+            expected("3:9", "let y = x;", "        ^")
+        );
+
+        // the gutter widens with the line number
+        let source = format!("{}x", "\n".repeat(9));
+        assert_eq!(render(&source, 9..10), expected("10:1", "x", "^"));
+    }
+
+    #[test]
+    fn render_tabs() {
+        let source = "\tlet x;\n";
+        assert_eq!(render(source, 5..6), expected("1:6", "\tlet x;", "\t    ^"));
+    }
+
+    #[test]
+    fn render_multibyte() {
+        let source = "let é = \"ü\";";
+
+        // a span over a multibyte character is one caret wide
+        assert_eq!(render(source, 4..6), expected("1:5", source, "    ^"));
+
+        // a span starting within a character starts at the character
+        assert_eq!(render(source, 5..6), expected("1:5", source, "    ^"));
+
+        // columns count characters rather than bytes
+        assert_eq!(render(source, 9..12), expected("1:9", source, "        ^^"));
+
+        // a span ending within a character ends before it
+        assert_eq!(render(source, 9..11), expected("1:9", source, "        ^"));
+    }
+
+    #[test]
+    fn render_multiple_lines() {
+        let source = "let x = [\n  1,\n];";
+        assert_eq!(
+            render(source, 8..17),
+            expected("1:9", "let x = [", "        ^")
+        );
+        assert_eq!(
+            render(source, 4..17),
+            expected("1:5", "let x = [", "    ^^^^^")
+        );
+    }
+
+    #[test]
+    fn render_end_of_file() {
+        let source = "let x = 1;";
+        assert_eq!(
+            render(source, 10..10),
+            expected("1:11", "let x = 1;", "          ^")
+        );
+
+        let source = "let x = 1;\n";
+        assert_eq!(render(source, 11..11), expected("2:1", "", "^"));
+    }
+
+    #[test]
+    fn render_out_of_range() {
+        let source = "a\nb";
+        assert_eq!(render(source, 100..200), expected("2:2", "b", " ^"));
+        assert_eq!(render(source, 2..200), expected("2:1", "b", "^"));
+
+        // an end before the start is treated as empty
+        assert_eq!(render(source, 2..0), expected("2:1", "b", "^"));
+    }
+
+    #[test]
+    fn char_boundary() {
+        let diagnostics = Diagnostics::new("input.js", "é!");
+        assert_eq!(diagnostics.char_boundary(0), 0);
+        assert_eq!(diagnostics.char_boundary(1), 0);
+        assert_eq!(diagnostics.char_boundary(2), 2);
+        assert_eq!(diagnostics.char_boundary(3), 3);
+        assert_eq!(diagnostics.char_boundary(4), 3);
+    }
+}
@@ -1,18 +1,36 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
 use super::js_boundary::JsMetaHandle;
-use crate::ir::js_boundary::{Constant, Instruction};
+use crate::ir::intrinsics::IntrinsicKind;
+use crate::ir::js_boundary::{
+    AtomicOperation, BlockId, CallingConvention, Comparison, Constant, FloatComparison,
+    GlobalInitializer, Instruction, JsMeta, MemoryOrdering, MethodLinkage, Register,
+    TypeDefinition, TypeId, ENTRY_SYMBOL,
+};
+use crate::ir::verify::{self, ControlFlow};
 use inkwell::{
+    attributes::{Attribute, AttributeLoc},
+    builder::Builder,
     context::Context,
     module::{Linkage, Module},
-    types::{FunctionType, IntType},
-    values::BasicValue,
-    values::FunctionValue,
+    passes::PassManager,
+    types::{BasicType, BasicTypeEnum, FunctionType},
+    values::{AggregateValueEnum, BasicValue, BasicValueEnum},
+    values::{CallableValue, FloatValue, FunctionValue, IntValue, PhiValue, PointerValue},
+    AddressSpace, AtomicOrdering, AtomicRMWBinOp, FloatPredicate, IntPredicate,
 };
 
+/// The LLVM representation of a type, which is `None` for `void`.
 pub struct LLVMType<'ctx> {
-    int_type: IntType<'ctx>,
-    signed: bool,
+    basic_type: Option<BasicTypeEnum<'ctx>>,
+}
+
+impl<'ctx> LLVMType<'ctx> {
+    fn basic(&self) -> BasicTypeEnum<'ctx> {
+        self.basic_type
+            .expect("expected a type that can hold a value, not void")
+    }
 }
 
 pub struct LLVMMethod<'ctx> {
@@ -20,39 +38,162 @@ pub struct LLVMMethod<'ctx> {
     method_impl: FunctionValue<'ctx>,
 }
 
-pub fn hydrate<'ctx>(meta: &JsMetaHandle, context: &'ctx Context, module: &mut Module<'ctx>) {
+/// A value held in a register, alongside the type it was produced as so that
+/// instructions can pick between signed and unsigned operations.
+#[derive(Clone, Copy)]
+pub struct LLVMRegister<'ctx> {
+    value: BasicValueEnum<'ctx>,
+    type_id: TypeId,
+}
+
+impl<'ctx> LLVMRegister<'ctx> {
+    fn new<V: BasicValue<'ctx>>(value: V, type_id: TypeId) -> Self {
+        Self {
+            value: value.as_basic_value_enum(),
+            type_id,
+        }
+    }
+
+    fn int(&self) -> IntValue<'ctx> {
+        self.value.into_int_value()
+    }
+
+    fn float(&self) -> FloatValue<'ctx> {
+        self.value.into_float_value()
+    }
+
+    fn pointer(&self) -> PointerValue<'ctx> {
+        self.value.into_pointer_value()
+    }
+}
+
+pub fn hydrate<'ctx>(
+    meta: &JsMetaHandle,
+    context: &'ctx Context,
+    module: &mut Module<'ctx>,
+) -> Result<(), String> {
     let meta = meta.lock().unwrap();
 
     // populate types
     let mut llvm_types = Vec::new();
     for r#type in meta.types.iter() {
-        llvm_types.push(LLVMType {
-            signed: r#type.signed,
-            int_type: context.custom_width_int_type(r#type.bits),
-        });
+        let basic_type = match r#type {
+            TypeDefinition::Integer { bits, .. } => {
+                Some(context.custom_width_int_type(*bits).into())
+            }
+            TypeDefinition::Float { bits: 16 } => Some(context.f16_type().into()),
+            TypeDefinition::Float { bits: 32 } => Some(context.f32_type().into()),
+            TypeDefinition::Float { bits: 64 } => Some(context.f64_type().into()),
+            TypeDefinition::Float { bits: 128 } => Some(context.f128_type().into()),
+            TypeDefinition::Float { bits } => panic!("unsupported float width {}", bits),
+            TypeDefinition::Void => None,
+            TypeDefinition::Pointer { pointee } => {
+                // a pointee's id is always lower than the pointer's, so it has
+                // already been populated
+                let pointee_type = llvm_types[*pointee as usize]
+                    .basic_type
+                    .unwrap_or_else(|| context.i8_type().into());
+
+                Some(pointee_type.ptr_type(AddressSpace::Generic).into())
+            }
+            TypeDefinition::Struct { name, fields } => {
+                let field_types = fields
+                    .iter()
+                    .map(|field| llvm_types[*field as usize].basic())
+                    .collect::<Vec<_>>();
+
+                let struct_type = context.opaque_struct_type(name);
+                struct_type.set_body(field_types.as_slice(), false);
+
+                Some(struct_type.into())
+            }
+            TypeDefinition::FunctionPointer {
+                return_type,
+                parameters,
+                variadic,
+            } => {
+                let fn_type =
+                    function_type(context, &llvm_types, *return_type, parameters, *variadic);
+                Some(fn_type.ptr_type(AddressSpace::Generic).into())
+            }
+            TypeDefinition::Array { element, length } => {
+                let element_type: BasicTypeEnum = llvm_types[*element as usize].basic();
+                Some(element_type.array_type(*length).into())
+            }
+        };
+
+        llvm_types.push(LLVMType { basic_type });
+    }
+
+    // populate globals
+    let mut llvm_globals = Vec::new();
+    for global in meta.globals.iter() {
+        let llvm_type = &llvm_types[global.type_id as usize];
+
+        let initializer = match &global.initializer {
+            GlobalInitializer::Zero => const_zero(llvm_type.basic()),
+            GlobalInitializer::Constant(constant) => {
+                const_value(&meta.types[global.type_id as usize], llvm_type, constant)
+            }
+            GlobalInitializer::Bytes(bytes) => {
+                let byte_type = context.i8_type();
+                let bytes = bytes
+                    .iter()
+                    .map(|byte| byte_type.const_int(*byte as u64, false))
+                    .collect::<Vec<_>>();
+
+                byte_type.const_array(bytes.as_slice()).into()
+            }
+        };
+
+        let name = global.symbol().unwrap_or_default();
+
+        let llvm_global = module.add_global(llvm_type.basic(), None, name.as_str());
+        llvm_global.set_initializer(&initializer);
+        llvm_global.set_constant(!global.mutable);
+
+        if global.is_literal() {
+            llvm_global.set_linkage(Linkage::Private);
+            llvm_global.set_unnamed_addr(true);
+        }
+
+        // the address of a global is a pointer to its type, except for
+        // literals which point to their first byte
+        let pointer_type = llvm_types[global.pointer_type as usize]
+            .basic()
+            .into_pointer_type();
+        llvm_globals.push(llvm_global.as_pointer_value().const_cast(pointer_type));
     }
 
     // populate methods *declarations*
     let mut llvm_methods = Vec::new();
     for method in meta.methods.iter() {
-        let return_type = &llvm_types[method.return_type as usize];
-        let parameter_types = method
-            .parameters
-            .iter()
-            .map(|p| &llvm_types[*p as usize])
-            .map(|t| t.int_type)
-            .map(|i| i.into())
-            .collect::<Vec<_>>();
+        let fn_type = function_type(
+            context,
+            &llvm_types,
+            method.return_type,
+            &method.parameters,
+            method.variadic,
+        );
 
-        let fn_type = return_type
-            .int_type
-            .fn_type(parameter_types.as_slice(), false);
+        let name = method.symbol();
 
-        let function = module.add_function(
-            format!("tinkerlang_{}", method.name).as_str(),
-            fn_type,
-            None,
-        );
+        let linkage = match method.options.linkage {
+            MethodLinkage::External => None,
+            MethodLinkage::Internal => Some(Linkage::Internal),
+            MethodLinkage::Weak => Some(Linkage::WeakAny),
+        };
+
+        let function = module.add_function(name.as_str(), fn_type, linkage);
+        function.set_call_conventions(calling_convention(method.options.calling_convention));
+
+        for attribute in method.options.attributes.iter() {
+            let kind_id = Attribute::get_named_enum_kind_id(attribute.name());
+            function.add_attribute(
+                AttributeLoc::Function,
+                context.create_enum_attribute(kind_id, 0),
+            );
+        }
 
         llvm_methods.push(LLVMMethod {
             method_type: fn_type,
@@ -70,38 +211,253 @@ pub fn hydrate<'ctx>(meta: &JsMetaHandle, context: &'ctx Context, module: &mut M
 
     let i32_type = context.i32_type();
     let fn_type = i32_type.fn_type(&[], false);
-    let function = module.add_function("main", fn_type, Some(Linkage::External));
+    let function = module.add_function(ENTRY_SYMBOL, fn_type, Some(Linkage::External));
     let basic_block = context.append_basic_block(function, "entry");
 
     builder.position_at_end(basic_block);
 
-    let retval = builder
-        .build_call(main.method_impl, &[], "call")
-        .try_as_basic_value()
-        .unwrap_left();
-    retval.set_name("retval");
+    let call = builder.build_call(main.method_impl, &[], "call");
+    call.set_call_convention(main.method_impl.get_call_conventions());
 
-    builder.build_return(Some(&retval));
+    let retval = call.try_as_basic_value().left();
+
+    match retval {
+        Some(retval) => {
+            retval.set_name("retval");
+            builder.build_return(Some(&retval));
+        }
+        // a void main exits successfully
+        None => {
+            builder.build_return(Some(&i32_type.const_zero()));
+        }
+    }
 
     // emit method declarations
     for (id, llvm_method) in llvm_methods.iter().enumerate() {
         let source = &meta.methods[id];
 
-        for block in source.blocks.iter() {
-            let llvm_block =
-                context.append_basic_block(llvm_method.method_impl, block.name.as_str());
-            builder.position_at_end(llvm_block);
+        if source.external {
+            continue;
+        }
+
+        let control_flow = ControlFlow::new(source);
+        let register_types = verify::register_types(&meta, source, &control_flow);
 
-            let mut registers = HashMap::new();
+        // every block is created before any are filled, so that branches may
+        // jump forward to blocks that haven't been emitted yet
+        let llvm_blocks = source
+            .blocks
+            .iter()
+            .map(|block| context.append_basic_block(llvm_method.method_impl, block.name.as_str()))
+            .collect::<Vec<_>>();
+
+        // locals are allocated up front in the entry block, where mem2reg is
+        // able to promote them into registers
+        let llvm_locals = match llvm_blocks.first() {
+            Some(entry) => {
+                builder.position_at_end(*entry);
+
+                source
+                    .locals
+                    .iter()
+                    .map(|type_id| builder.build_alloca(llvm_types[*type_id as usize].basic(), ""))
+                    .collect::<Vec<_>>()
+            }
+            None => vec![],
+        };
+
+        // registers live for the whole method. blocks are filled in an order
+        // where a block's dominators come first, so any register a block may
+        // use has already been emitted by the time it's reached.
+        let mut registers: HashMap<Register, LLVMRegister> = HashMap::new();
+
+        // incoming values of a phi may come from blocks that haven't been
+        // emitted yet, so they're only attached once every block is filled
+        let mut phis: Vec<(PhiValue, BlockId, &Vec<(Register, BlockId)>)> = Vec::new();
+
+        for block_id in control_flow.emission_order() {
+            let block = &source.blocks[block_id as usize];
+            builder.position_at_end(llvm_blocks[block_id as usize]);
 
             for inst in block.instructions.iter() {
                 match inst {
                     Instruction::Add { result, a, b } => {
-                        let a = registers.get(a).unwrap();
-                        let b = registers.get(b).unwrap();
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_int_add(a.int(), b.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Sub { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_int_sub(a.int(), b.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Mul { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_int_mul(a.int(), b.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Div { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = if meta.types[a.type_id as usize].is_signed() {
+                            builder.build_int_signed_div(a.int(), b.int(), "")
+                        } else {
+                            builder.build_int_unsigned_div(a.int(), b.int(), "")
+                        };
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Rem { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = if meta.types[a.type_id as usize].is_signed() {
+                            builder.build_int_signed_rem(a.int(), b.int(), "")
+                        } else {
+                            builder.build_int_unsigned_rem(a.int(), b.int(), "")
+                        };
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Neg { result, source } => {
+                        let source = registers[source];
+
+                        let value = builder.build_int_neg(source.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, source.type_id));
+                    }
+                    Instruction::And { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_and(a.int(), b.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Or { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_or(a.int(), b.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Xor { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_xor(a.int(), b.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::ShiftLeft { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_left_shift(a.int(), b.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::ShiftRight { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        // arithmetic shifts preserve the sign of signed integers
+                        let sign_extend = meta.types[a.type_id as usize].is_signed();
+                        let value = builder.build_right_shift(a.int(), b.int(), sign_extend, "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Not { result, source } => {
+                        let source = registers[source];
+
+                        let value = builder.build_not(source.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, source.type_id));
+                    }
+                    Instruction::FAdd { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
 
-                        let result_reg = builder.build_int_add(*a, *b, "");
-                        registers.insert(*result, result_reg);
+                        let value = builder.build_float_add(a.float(), b.float(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::FSub { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_float_sub(a.float(), b.float(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::FMul { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_float_mul(a.float(), b.float(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::FDiv { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_float_div(a.float(), b.float(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::FRem { result, a, b } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value = builder.build_float_rem(a.float(), b.float(), "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::FNeg { result, source } => {
+                        let source = registers[source];
+
+                        let value = builder.build_float_neg(source.float(), "");
+                        registers.insert(*result, LLVMRegister::new(value, source.type_id));
+                    }
+                    Instruction::FloatCompare {
+                        result,
+                        comparison,
+                        a,
+                        b,
+                    } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let predicate = float_predicate(*comparison);
+                        let value =
+                            builder.build_float_compare(predicate, a.float(), b.float(), "");
+                        registers.insert(*result, LLVMRegister::new(value, meta.bool_type));
+                    }
+                    Instruction::IntToFloat {
+                        result,
+                        source,
+                        convert_into,
+                    } => {
+                        let source = registers[source];
+                        let float_type =
+                            llvm_types[*convert_into as usize].basic().into_float_type();
+
+                        let value = if meta.types[source.type_id as usize].is_signed() {
+                            builder.build_signed_int_to_float(source.int(), float_type, "")
+                        } else {
+                            builder.build_unsigned_int_to_float(source.int(), float_type, "")
+                        };
+                        registers.insert(*result, LLVMRegister::new(value, *convert_into));
+                    }
+                    Instruction::FloatToInt {
+                        result,
+                        source,
+                        convert_into,
+                    } => {
+                        let source = registers[source];
+                        let int_type = llvm_types[*convert_into as usize].basic().into_int_type();
+
+                        let value = if meta.types[*convert_into as usize].is_signed() {
+                            builder.build_float_to_signed_int(source.float(), int_type, "")
+                        } else {
+                            builder.build_float_to_unsigned_int(source.float(), int_type, "")
+                        };
+                        registers.insert(*result, LLVMRegister::new(value, *convert_into));
                     }
                     Instruction::LoadParameter {
                         result,
@@ -111,28 +467,23 @@ pub fn hydrate<'ctx>(meta: &JsMetaHandle, context: &'ctx Context, module: &mut M
                             .method_impl
                             .get_nth_param(*parameter_number as u32)
                             .unwrap();
-                        let param = param.into_int_value();
 
                         let source_param_id = source.parameters[*parameter_number as usize];
-                        let param_type = &llvm_types[source_param_id as usize];
-
-                        let result_reg =
-                            builder.build_int_add(param_type.int_type.const_zero(), param, "");
-                        registers.insert(*result, result_reg);
+                        registers.insert(*result, LLVMRegister::new(param, source_param_id));
                     }
                     Instruction::LoadConstant {
                         result,
                         type_id,
                         constant,
-                    } => match constant {
-                        Constant::Number(number) => {
-                            let llvm_type = &llvm_types[*type_id as usize];
-                            let value = llvm_type.int_type.const_int(*number as u64, *number < 0);
-                            let const_reg =
-                                builder.build_int_add(llvm_type.int_type.const_zero(), value, "");
-                            registers.insert(*result, const_reg);
-                        }
-                    },
+                    } => {
+                        let value = const_value(
+                            &meta.types[*type_id as usize],
+                            &llvm_types[*type_id as usize],
+                            constant,
+                        );
+
+                        registers.insert(*result, LLVMRegister::new(value, *type_id));
+                    }
                     Instruction::Call {
                         result,
                         method_id,
@@ -141,44 +492,916 @@ pub fn hydrate<'ctx>(meta: &JsMetaHandle, context: &'ctx Context, module: &mut M
                         let function = &llvm_methods[*method_id as usize];
                         let parameters = parameters
                             .iter()
-                            .map(|r| registers.get(r).unwrap().as_basic_value_enum())
+                            .map(|r| registers[r].value)
                             .collect::<Vec<_>>();
 
                         let result_reg =
                             builder.build_call(function.method_impl, parameters.as_slice(), "");
-                        registers.insert(
-                            *result,
-                            result_reg
-                                .try_as_basic_value()
-                                .unwrap_left()
-                                .into_int_value(),
+                        result_reg.set_call_convention(function.method_impl.get_call_conventions());
+
+                        if let Some(result) = result {
+                            let callee = &meta.methods[*method_id as usize];
+                            let value = result_reg.try_as_basic_value().left().unwrap_or_else(|| {
+                                panic!(
+                                    "method `{}` returns void, so its result cannot be stored in register {}",
+                                    callee.name, result
+                                )
+                            });
+
+                            registers.insert(*result, LLVMRegister::new(value, callee.return_type));
+                        }
+                    }
+                    Instruction::MethodAddress { result, method_id } => {
+                        let value = llvm_methods[*method_id as usize]
+                            .method_impl
+                            .as_global_value()
+                            .as_pointer_value();
+                        registers.insert(*result, LLVMRegister::new(value, register_types[result]));
+                    }
+                    Instruction::CallIndirect {
+                        result,
+                        pointer,
+                        parameters,
+                    } => {
+                        let callee = CallableValue::try_from(registers[pointer].pointer())
+                            .expect("expected a pointer to a function");
+                        let parameters = parameters
+                            .iter()
+                            .map(|r| registers[r].value)
+                            .collect::<Vec<_>>();
+
+                        let result_reg = builder.build_call(callee, parameters.as_slice(), "");
+
+                        if let Some(result) = result {
+                            let value = result_reg.try_as_basic_value().left().unwrap_or_else(|| {
+                                panic!(
+                                    "register {} points to a method returning void, so its result cannot be stored in register {}",
+                                    pointer, result
+                                )
+                            });
+
+                            registers
+                                .insert(*result, LLVMRegister::new(value, register_types[result]));
+                        }
+                    }
+                    Instruction::Switch {
+                        condition,
+                        default,
+                        cases,
+                    } => {
+                        let condition = registers[condition];
+                        let definition = &meta.types[condition.type_id as usize];
+
+                        // the verifier ensures the condition is an integer,
+                        // and that every case fits in it exactly once
+                        let cases = cases
+                            .iter()
+                            .map(|(constant, block_id)| {
+                                let value = const_value(
+                                    definition,
+                                    &llvm_types[condition.type_id as usize],
+                                    constant,
+                                );
+
+                                (value.into_int_value(), llvm_blocks[*block_id as usize])
+                            })
+                            .collect::<Vec<_>>();
+
+                        builder.build_switch(
+                            condition.int(),
+                            llvm_blocks[*default as usize],
+                            cases.as_slice(),
                         );
                     }
+                    Instruction::Select {
+                        result,
+                        condition,
+                        a,
+                        b,
+                    } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let value =
+                            builder.build_select(registers[condition].int(), a.value, b.value, "");
+                        registers.insert(*result, LLVMRegister::new(value, a.type_id));
+                    }
+                    Instruction::Unreachable => {
+                        builder.build_unreachable();
+                    }
+                    Instruction::AddChecked {
+                        result,
+                        overflow,
+                        a,
+                        b,
+                    }
+                    | Instruction::SubChecked {
+                        result,
+                        overflow,
+                        a,
+                        b,
+                    }
+                    | Instruction::MulChecked {
+                        result,
+                        overflow,
+                        a,
+                        b,
+                    } => {
+                        let operation = match inst {
+                            Instruction::AddChecked { .. } => "add",
+                            Instruction::SubChecked { .. } => "sub",
+                            _ => "mul",
+                        };
+
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let signed = meta.types[a.type_id as usize].is_signed();
+                        let int_type = a.int().get_type();
+
+                        // e.g. `llvm.sadd.with.overflow.i32`, which returns
+                        // `{ i32, i1 }`
+                        let name = format!(
+                            "llvm.{}{}.with.overflow.i{}",
+                            if signed { "s" } else { "u" },
+                            operation,
+                            int_type.get_bit_width()
+                        );
+                        let return_type = context
+                            .struct_type(&[int_type.into(), context.bool_type().into()], false);
+                        let function = intrinsic(
+                            module,
+                            name.as_str(),
+                            return_type.fn_type(&[int_type.into(), int_type.into()], false),
+                        );
+
+                        let value = builder
+                            .build_call(function, &[a.value, b.value], "")
+                            .try_as_basic_value()
+                            .left()
+                            .expect("expected overflow intrinsic to return a value")
+                            .into_struct_value();
+
+                        let sum = builder
+                            .build_extract_value(value, 0, "")
+                            .expect("expected overflow intrinsic to return a result");
+                        let overflowed = builder
+                            .build_extract_value(value, 1, "")
+                            .expect("expected overflow intrinsic to return an overflow flag");
+
+                        registers.insert(*result, LLVMRegister::new(sum, a.type_id));
+                        registers.insert(*overflow, LLVMRegister::new(overflowed, meta.bool_type));
+                    }
+                    Instruction::Intrinsic {
+                        result,
+                        name,
+                        arguments,
+                    } => {
+                        let arguments = arguments.iter().map(|r| registers[r]).collect::<Vec<_>>();
+
+                        let value = build_intrinsic(
+                            context,
+                            module,
+                            &builder,
+                            &meta,
+                            name.as_str(),
+                            arguments.as_slice(),
+                        );
+
+                        if let Some(result) = result {
+                            let value = value.expect(
+                                "expected an intrinsic with a result register to produce a value",
+                            );
+
+                            registers
+                                .insert(*result, LLVMRegister::new(value, register_types[result]));
+                        }
+                    }
+                    Instruction::AtomicLoad {
+                        result,
+                        pointer,
+                        ordering,
+                    } => {
+                        let value = builder.build_load(registers[pointer].pointer(), "");
+                        value
+                            .as_instruction_value()
+                            .expect("expected a load instruction")
+                            .set_atomic_ordering(memory_ordering(*ordering))
+                            .expect("expected an atomic load of a verified type");
+
+                        registers.insert(*result, LLVMRegister::new(value, register_types[result]));
+                    }
+                    Instruction::AtomicStore {
+                        pointer,
+                        source,
+                        ordering,
+                    } => {
+                        builder
+                            .build_store(registers[pointer].pointer(), registers[source].value)
+                            .set_atomic_ordering(memory_ordering(*ordering))
+                            .expect("expected an atomic store of a verified type");
+                    }
+                    Instruction::AtomicRmw {
+                        result,
+                        operation,
+                        pointer,
+                        source: operand,
+                        ordering,
+                    } => {
+                        let operand = registers[operand];
+                        let definition = &meta.types[operand.type_id as usize];
+                        let operation = atomic_operation(*operation, definition.is_signed());
+                        let ordering = memory_ordering(*ordering);
+
+                        let value = match definition {
+                            // floats can only be exchanged, which is done with
+                            // their bits, as inkwell only builds `atomicrmw`
+                            // on integers
+                            TypeDefinition::Float { bits } => {
+                                let int_type = context.custom_width_int_type(*bits);
+                                let pointer = builder.build_pointer_cast(
+                                    registers[pointer].pointer(),
+                                    int_type.ptr_type(AddressSpace::Generic),
+                                    "",
+                                );
+                                let operand_bits =
+                                    builder.build_bitcast(operand.value, int_type, "");
+
+                                let old = builder
+                                    .build_atomicrmw(
+                                        operation,
+                                        pointer,
+                                        operand_bits.into_int_value(),
+                                        ordering,
+                                    )
+                                    .expect("expected an atomic_rmw of a verified type");
+
+                                builder.build_bitcast(old, operand.value.get_type(), "")
+                            }
+                            _ => builder
+                                .build_atomicrmw(
+                                    operation,
+                                    registers[pointer].pointer(),
+                                    operand.int(),
+                                    ordering,
+                                )
+                                .expect("expected an atomic_rmw of a verified type")
+                                .into(),
+                        };
+
+                        registers.insert(*result, LLVMRegister::new(value, operand.type_id));
+                    }
+                    Instruction::CompareExchange {
+                        result,
+                        success,
+                        pointer,
+                        expected,
+                        replacement,
+                        success_ordering,
+                        failure_ordering,
+                    } => {
+                        let expected = registers[expected];
+
+                        let value = builder
+                            .build_cmpxchg(
+                                registers[pointer].pointer(),
+                                expected.value,
+                                registers[replacement].value,
+                                memory_ordering(*success_ordering),
+                                memory_ordering(*failure_ordering),
+                            )
+                            .expect("expected a cmpxchg of a verified type");
+
+                        let old = builder
+                            .build_extract_value(value, 0, "")
+                            .expect("expected cmpxchg to return the old value");
+                        let replaced = builder
+                            .build_extract_value(value, 1, "")
+                            .expect("expected cmpxchg to return whether it succeeded");
+
+                        registers.insert(*result, LLVMRegister::new(old, expected.type_id));
+                        registers.insert(*success, LLVMRegister::new(replaced, meta.bool_type));
+                    }
+                    Instruction::Fence { ordering } => {
+                        builder.build_fence(memory_ordering(*ordering), 0, "");
+                    }
+                    Instruction::Trap => {
+                        let function =
+                            intrinsic(module, "llvm.trap", context.void_type().fn_type(&[], false));
+
+                        builder.build_call(function, &[], "");
+                    }
                     Instruction::Return { result } => {
                         let ret = result
                             .and_then(|register| registers.get(&register))
-                            .map(|v| -> Box<dyn BasicValue> { Box::new(*v) });
+                            .map(|v| v.value);
 
-                        builder.build_return(ret.as_deref());
+                        builder.build_return(ret.as_ref().map(|v| v as &dyn BasicValue));
                     }
                     Instruction::Truncate {
                         result,
                         truncate_into,
                         source,
                     } => {
-                        let source = registers.get(source).unwrap();
-                        let truncate_into = &llvm_types[*truncate_into as usize];
+                        let source = registers[source];
+                        let truncate_type = llvm_types[*truncate_into as usize].basic();
 
-                        let result_reg =
-                            builder.build_int_truncate(*source, truncate_into.int_type, "");
-                        registers.insert(*result, result_reg);
+                        let result_reg = builder.build_int_truncate(
+                            source.int(),
+                            truncate_type.into_int_type(),
+                            "",
+                        );
+                        registers.insert(*result, LLVMRegister::new(result_reg, *truncate_into));
+                    }
+                    Instruction::SignExtend {
+                        result,
+                        source,
+                        extend_into,
+                    } => {
+                        let source = registers[source];
+                        let extend_type = llvm_types[*extend_into as usize].basic();
+
+                        let value = builder.build_int_s_extend(
+                            source.int(),
+                            extend_type.into_int_type(),
+                            "",
+                        );
+                        registers.insert(*result, LLVMRegister::new(value, *extend_into));
+                    }
+                    Instruction::ZeroExtend {
+                        result,
+                        source,
+                        extend_into,
+                    } => {
+                        let source = registers[source];
+                        let extend_type = llvm_types[*extend_into as usize].basic();
+
+                        let value = builder.build_int_z_extend(
+                            source.int(),
+                            extend_type.into_int_type(),
+                            "",
+                        );
+                        registers.insert(*result, LLVMRegister::new(value, *extend_into));
+                    }
+                    Instruction::Cast {
+                        result,
+                        source,
+                        cast_into,
+                    } => {
+                        let source = registers[source];
+                        let cast_type = llvm_types[*cast_into as usize].basic();
+
+                        let from = &meta.types[source.type_id as usize];
+                        let to = &meta.types[*cast_into as usize];
+
+                        let value: BasicValueEnum = match (from, to) {
+                            (TypeDefinition::Integer { .. }, TypeDefinition::Integer { .. }) => {
+                                let int_type = cast_type.into_int_type();
+
+                                if to.bits() < from.bits() {
+                                    builder
+                                        .build_int_truncate(source.int(), int_type, "")
+                                        .into()
+                                } else if to.bits() == from.bits() {
+                                    source.value
+                                } else if from.is_signed() {
+                                    builder
+                                        .build_int_s_extend(source.int(), int_type, "")
+                                        .into()
+                                } else {
+                                    builder
+                                        .build_int_z_extend(source.int(), int_type, "")
+                                        .into()
+                                }
+                            }
+                            (TypeDefinition::Float { .. }, TypeDefinition::Float { .. }) => {
+                                let float_type = cast_type.into_float_type();
+
+                                if to.bits() < from.bits() {
+                                    builder
+                                        .build_float_trunc(source.float(), float_type, "")
+                                        .into()
+                                } else if to.bits() == from.bits() {
+                                    source.value
+                                } else {
+                                    builder
+                                        .build_float_ext(source.float(), float_type, "")
+                                        .into()
+                                }
+                            }
+                            (TypeDefinition::Integer { .. }, TypeDefinition::Float { .. }) => {
+                                let float_type = cast_type.into_float_type();
+
+                                if from.is_signed() {
+                                    builder
+                                        .build_signed_int_to_float(source.int(), float_type, "")
+                                        .into()
+                                } else {
+                                    builder
+                                        .build_unsigned_int_to_float(source.int(), float_type, "")
+                                        .into()
+                                }
+                            }
+                            (TypeDefinition::Float { .. }, TypeDefinition::Integer { .. }) => {
+                                let int_type = cast_type.into_int_type();
+
+                                if to.is_signed() {
+                                    builder
+                                        .build_float_to_signed_int(source.float(), int_type, "")
+                                        .into()
+                                } else {
+                                    builder
+                                        .build_float_to_unsigned_int(source.float(), int_type, "")
+                                        .into()
+                                }
+                            }
+                            (TypeDefinition::Pointer { .. }, TypeDefinition::Pointer { .. }) => {
+                                builder.build_bitcast(source.value, cast_type, "")
+                            }
+                            _ => panic!("cannot cast {:?} into {:?}", from, to),
+                        };
+                        registers.insert(*result, LLVMRegister::new(value, *cast_into));
+                    }
+                    Instruction::Bitcast {
+                        result,
+                        source,
+                        cast_into,
+                    } => {
+                        let source = registers[source];
+                        let cast_type = llvm_types[*cast_into as usize].basic();
+
+                        let value = builder.build_bitcast(source.value, cast_type, "");
+                        registers.insert(*result, LLVMRegister::new(value, *cast_into));
                     }
+                    Instruction::Compare {
+                        result,
+                        comparison,
+                        a,
+                        b,
+                    } => {
+                        let a = registers[a];
+                        let b = registers[b];
+
+                        let predicate =
+                            int_predicate(*comparison, meta.types[a.type_id as usize].is_signed());
+                        let value = builder.build_int_compare(predicate, a.int(), b.int(), "");
+                        registers.insert(*result, LLVMRegister::new(value, meta.bool_type));
+                    }
+                    Instruction::Branch { target } => {
+                        builder.build_unconditional_branch(llvm_blocks[*target as usize]);
+                    }
+                    Instruction::ConditionalBranch {
+                        condition,
+                        then_block,
+                        else_block,
+                    } => {
+                        builder.build_conditional_branch(
+                            registers[condition].int(),
+                            llvm_blocks[*then_block as usize],
+                            llvm_blocks[*else_block as usize],
+                        );
+                    }
+                    Instruction::Phi {
+                        result,
+                        type_id,
+                        incoming,
+                    } => {
+                        let llvm_type = &llvm_types[*type_id as usize];
+
+                        let phi = builder.build_phi(llvm_type.basic(), "");
+                        registers
+                            .insert(*result, LLVMRegister::new(phi.as_basic_value(), *type_id));
+                        phis.push((phi, block_id, incoming));
+                    }
+                    Instruction::LoadLocal { result, local_id } => {
+                        let type_id = source.locals[*local_id as usize];
+
+                        let value = builder.build_load(llvm_locals[*local_id as usize], "");
+                        registers.insert(*result, LLVMRegister::new(value, type_id));
+                    }
+                    Instruction::StoreLocal { local_id, source } => {
+                        builder
+                            .build_store(llvm_locals[*local_id as usize], registers[source].value);
+                    }
+                    Instruction::AddressOf { result, local_id } => {
+                        let value = llvm_locals[*local_id as usize];
+                        registers.insert(*result, LLVMRegister::new(value, register_types[result]));
+                    }
+                    Instruction::LoadPointer { result, pointer } => {
+                        let pointer = registers[pointer];
+
+                        let value = builder.build_load(pointer.pointer(), "");
+                        registers.insert(*result, LLVMRegister::new(value, register_types[result]));
+                    }
+                    Instruction::StorePointer { pointer, source } => {
+                        builder.build_store(registers[pointer].pointer(), registers[source].value);
+                    }
+                    Instruction::Offset {
+                        result,
+                        pointer,
+                        index,
+                    } => {
+                        let pointer = registers[pointer];
+                        let index = registers[index];
+
+                        // offsets aren't marked `inbounds`, as lowerers may
+                        // step outside of an allocation before stepping back
+                        let value =
+                            unsafe { builder.build_gep(pointer.pointer(), &[index.int()], "") };
+                        registers.insert(*result, LLVMRegister::new(value, pointer.type_id));
+                    }
+                    Instruction::PointerToInt {
+                        result,
+                        source,
+                        convert_into,
+                    } => {
+                        let source = registers[source];
+                        let int_type = llvm_types[*convert_into as usize].basic().into_int_type();
+
+                        let value = builder.build_ptr_to_int(source.pointer(), int_type, "");
+                        registers.insert(*result, LLVMRegister::new(value, *convert_into));
+                    }
+                    Instruction::IntToPointer {
+                        result,
+                        source,
+                        convert_into,
+                    } => {
+                        let source = registers[source];
+                        let pointer_type = llvm_types[*convert_into as usize]
+                            .basic()
+                            .into_pointer_type();
+
+                        let value = builder.build_int_to_ptr(source.int(), pointer_type, "");
+                        registers.insert(*result, LLVMRegister::new(value, *convert_into));
+                    }
+                    Instruction::GlobalAddress { result, global_id } => {
+                        let value = llvm_globals[*global_id as usize];
+                        registers.insert(*result, LLVMRegister::new(value, register_types[result]));
+                    }
+                    Instruction::LoadZero { result, type_id } => {
+                        let value = const_zero(llvm_types[*type_id as usize].basic());
+                        registers.insert(*result, LLVMRegister::new(value, *type_id));
+                    }
+                    Instruction::ExtractField {
+                        result,
+                        aggregate,
+                        index,
+                    } => {
+                        let value = match registers[aggregate].value {
+                            BasicValueEnum::StructValue(value) => {
+                                builder.build_extract_value(value, *index, "")
+                            }
+                            BasicValueEnum::ArrayValue(value) => {
+                                builder.build_extract_value(value, *index, "")
+                            }
+                            _ => None,
+                        }
+                        .expect("expected to extract a field of an aggregate");
+
+                        registers.insert(*result, LLVMRegister::new(value, register_types[result]));
+                    }
+                    Instruction::InsertField {
+                        result,
+                        aggregate,
+                        index,
+                        source,
+                    } => {
+                        let aggregate = registers[aggregate];
+                        let source = registers[source].value;
+
+                        let value: BasicValueEnum = match aggregate.value {
+                            BasicValueEnum::StructValue(value) => {
+                                builder.build_insert_value(value, source, *index, "")
+                            }
+                            BasicValueEnum::ArrayValue(value) => {
+                                builder.build_insert_value(value, source, *index, "")
+                            }
+                            _ => None,
+                        }
+                        .map(|value| match value {
+                            AggregateValueEnum::StructValue(value) => value.into(),
+                            AggregateValueEnum::ArrayValue(value) => value.into(),
+                        })
+                        .expect("expected to insert a field into an aggregate");
+
+                        registers.insert(*result, LLVMRegister::new(value, aggregate.type_id));
+                    }
+                    Instruction::FieldPointer {
+                        result,
+                        pointer,
+                        index,
+                    } => {
+                        let i32_type = context.i32_type();
+                        let indices = [
+                            i32_type.const_zero(),
+                            i32_type.const_int(*index as u64, false),
+                        ];
+
+                        // the field was checked to exist when typing the
+                        // registers, so the pointer stays within the aggregate
+                        let value = unsafe {
+                            builder.build_in_bounds_gep(registers[pointer].pointer(), &indices, "")
+                        };
+                        registers.insert(*result, LLVMRegister::new(value, register_types[result]));
+                    }
+                }
+            }
+        }
+
+        // LLVM expects an incoming value for every edge into the block, so a
+        // predecessor that branches here more than once repeats its value
+        for (phi, phi_block, incoming) in phis {
+            for (register, block_id) in incoming.iter() {
+                let value = registers[register].value;
+                let edges = control_flow
+                    .predecessors(phi_block)
+                    .iter()
+                    .filter(|predecessor| *predecessor == block_id)
+                    .count();
+
+                for _ in 0..edges {
+                    phi.add_incoming(&[(&value, llvm_blocks[*block_id as usize])]);
                 }
             }
         }
     }
 
+    // the verifier should catch anything LLVM would reject, but if it misses
+    // something, it's reported rather than miscompiled
+    module
+        .verify()
+        .map_err(|message| format!("LLVM rejected the hydrated module: {}", message))?;
+
+    // lowerers are free to use locals naively, so promote them to registers
+    let pass_manager = PassManager::create(&*module);
+    pass_manager.add_promote_memory_to_register_pass();
+    pass_manager.initialize();
+
+    for (llvm_method, source) in llvm_methods.iter().zip(meta.methods.iter()) {
+        if !source.external {
+            pass_manager.run_on(&llvm_method.method_impl);
+        }
+    }
+
+    pass_manager.finalize();
+
     println!(" ==> HYDRATION ==>");
     println!("JsMeta: {:#?}", meta);
     println!(" <== HYDRATION <==");
+
+    Ok(())
+}
+
+/// Calls the intrinsic known as `name`, returning the value it produces, if
+/// any. The verifier has already checked that the arguments suit it.
+fn build_intrinsic<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    meta: &JsMeta,
+    name: &str,
+    arguments: &[LLVMRegister<'ctx>],
+) -> Option<BasicValueEnum<'ctx>> {
+    let kind = IntrinsicKind::parse(name).expect("expected intrinsic to have been verified");
+
+    let bool_type = context.bool_type();
+    let (full_name, fn_type, values): (_, _, Vec<BasicValueEnum>) = match kind {
+        IntrinsicKind::IntegerUnary | IntrinsicKind::CountZeros => {
+            let value = arguments[0].int();
+            let int_type = value.get_type();
+            let bits = int_type.get_bit_width();
+
+            let full_name = format!("llvm.{}.i{}", name, bits);
+
+            match kind {
+                // zero is given a defined result, rather than being poison
+                IntrinsicKind::CountZeros => (
+                    full_name,
+                    int_type.fn_type(&[int_type.into(), bool_type.into()], false),
+                    vec![value.into(), bool_type.const_zero().into()],
+                ),
+                _ => (
+                    full_name,
+                    int_type.fn_type(&[int_type.into()], false),
+                    vec![value.into()],
+                ),
+            }
+        }
+        IntrinsicKind::FloatUnary | IntrinsicKind::FloatBinary => {
+            let float_type = arguments[0].float().get_type();
+            let parameter_types: Vec<BasicTypeEnum> = vec![float_type.into(); arguments.len()];
+
+            (
+                format!(
+                    "llvm.{}.f{}",
+                    name,
+                    meta.types[arguments[0].type_id as usize].bits()
+                ),
+                float_type.fn_type(parameter_types.as_slice(), false),
+                arguments.iter().map(|argument| argument.value).collect(),
+            )
+        }
+        IntrinsicKind::MemoryTransfer | IntrinsicKind::MemorySet => {
+            let byte_pointer_type = context.i8_type().ptr_type(AddressSpace::Generic);
+            let dest = builder.build_pointer_cast(arguments[0].pointer(), byte_pointer_type, "");
+            let bytes = arguments[2].int();
+            let bits = bytes.get_type().get_bit_width();
+
+            // the trailing `i1` is whether the access is volatile
+            match kind {
+                IntrinsicKind::MemoryTransfer => {
+                    let source =
+                        builder.build_pointer_cast(arguments[1].pointer(), byte_pointer_type, "");
+
+                    (
+                        format!("llvm.{}.p0i8.p0i8.i{}", name, bits),
+                        context.void_type().fn_type(
+                            &[
+                                byte_pointer_type.into(),
+                                byte_pointer_type.into(),
+                                bytes.get_type().into(),
+                                bool_type.into(),
+                            ],
+                            false,
+                        ),
+                        vec![
+                            dest.into(),
+                            source.into(),
+                            bytes.into(),
+                            bool_type.const_zero().into(),
+                        ],
+                    )
+                }
+                _ => (
+                    format!("llvm.memset.p0i8.i{}", bits),
+                    context.void_type().fn_type(
+                        &[
+                            byte_pointer_type.into(),
+                            context.i8_type().into(),
+                            bytes.get_type().into(),
+                            bool_type.into(),
+                        ],
+                        false,
+                    ),
+                    vec![
+                        dest.into(),
+                        arguments[1].value,
+                        bytes.into(),
+                        bool_type.const_zero().into(),
+                    ],
+                ),
+            }
+        }
+    };
+
+    let function = intrinsic(module, full_name.as_str(), fn_type);
+
+    builder
+        .build_call(function, values.as_slice(), "")
+        .try_as_basic_value()
+        .left()
+}
+
+/// Gets an LLVM intrinsic, declaring it the first time it's used.
+fn intrinsic<'ctx>(
+    module: &Module<'ctx>,
+    name: &str,
+    fn_type: FunctionType<'ctx>,
+) -> FunctionValue<'ctx> {
+    module
+        .get_function(name)
+        .unwrap_or_else(|| module.add_function(name, fn_type, None))
+}
+
+fn function_type<'ctx>(
+    context: &'ctx Context,
+    llvm_types: &[LLVMType<'ctx>],
+    return_type: TypeId,
+    parameters: &[TypeId],
+    variadic: bool,
+) -> FunctionType<'ctx> {
+    let return_type = &llvm_types[return_type as usize];
+    let parameter_types = parameters
+        .iter()
+        .map(|p| &llvm_types[*p as usize])
+        .map(|t| t.basic())
+        .map(|i| i.into())
+        .collect::<Vec<_>>();
+
+    match return_type.basic_type {
+        Some(basic_type) => basic_type.fn_type(parameter_types.as_slice(), variadic),
+        None => context
+            .void_type()
+            .fn_type(parameter_types.as_slice(), variadic),
+    }
+}
+
+/// The value of a number constant, as the given type.
+fn const_value<'ctx>(
+    definition: &TypeDefinition,
+    llvm_type: &LLVMType<'ctx>,
+    constant: &Constant,
+) -> BasicValueEnum<'ctx> {
+    match definition {
+        TypeDefinition::Integer { signed, bits } => {
+            let int_type = llvm_type.basic().into_int_type();
+            let words = constant
+                .to_words(*signed, *bits)
+                .unwrap_or_else(|message| panic!("{}", message));
+
+            match words.as_slice() {
+                [word] => int_type.const_int(*word, false).into(),
+                _ => int_type.const_int_arbitrary_precision(&words).into(),
+            }
+        }
+        TypeDefinition::Float { .. } => llvm_type
+            .basic()
+            .into_float_type()
+            .const_float(constant.to_f64())
+            .into(),
+        TypeDefinition::Void => panic!("constants cannot be void"),
+        _ => panic!("constants cannot be pointers or aggregates, use `ld_zero` instead"),
+    }
+}
+
+/// The value of the given type where every bit is zero.
+fn const_zero(basic_type: BasicTypeEnum) -> BasicValueEnum {
+    match basic_type {
+        BasicTypeEnum::ArrayType(t) => t.const_zero().into(),
+        BasicTypeEnum::FloatType(t) => t.const_zero().into(),
+        BasicTypeEnum::IntType(t) => t.const_zero().into(),
+        BasicTypeEnum::PointerType(t) => t.const_zero().into(),
+        BasicTypeEnum::StructType(t) => t.const_zero().into(),
+        BasicTypeEnum::VectorType(t) => t.const_zero().into(),
+    }
+}
+
+fn calling_convention(convention: CallingConvention) -> u32 {
+    // the values of LLVM's `CallingConv::ID`
+    match convention {
+        CallingConvention::C => 0,
+        CallingConvention::Fast => 8,
+        CallingConvention::Cold => 9,
+    }
+}
+
+fn memory_ordering(ordering: MemoryOrdering) -> AtomicOrdering {
+    match ordering {
+        MemoryOrdering::Unordered => AtomicOrdering::Unordered,
+        MemoryOrdering::Monotonic => AtomicOrdering::Monotonic,
+        MemoryOrdering::Acquire => AtomicOrdering::Acquire,
+        MemoryOrdering::Release => AtomicOrdering::Release,
+        MemoryOrdering::AcquireRelease => AtomicOrdering::AcquireRelease,
+        MemoryOrdering::SequentiallyConsistent => AtomicOrdering::SequentiallyConsistent,
+    }
+}
+
+fn atomic_operation(operation: AtomicOperation, signed: bool) -> AtomicRMWBinOp {
+    match (operation, signed) {
+        (AtomicOperation::Exchange, _) => AtomicRMWBinOp::Xchg,
+        (AtomicOperation::Add, _) => AtomicRMWBinOp::Add,
+        (AtomicOperation::Sub, _) => AtomicRMWBinOp::Sub,
+        (AtomicOperation::And, _) => AtomicRMWBinOp::And,
+        (AtomicOperation::Nand, _) => AtomicRMWBinOp::Nand,
+        (AtomicOperation::Or, _) => AtomicRMWBinOp::Or,
+        (AtomicOperation::Xor, _) => AtomicRMWBinOp::Xor,
+        (AtomicOperation::Max, true) => AtomicRMWBinOp::Max,
+        (AtomicOperation::Max, false) => AtomicRMWBinOp::UMax,
+        (AtomicOperation::Min, true) => AtomicRMWBinOp::Min,
+        (AtomicOperation::Min, false) => AtomicRMWBinOp::UMin,
+    }
+}
+
+fn int_predicate(comparison: Comparison, signed: bool) -> IntPredicate {
+    match (comparison, signed) {
+        (Comparison::Equal, _) => IntPredicate::EQ,
+        (Comparison::NotEqual, _) => IntPredicate::NE,
+        (Comparison::LessThan, true) => IntPredicate::SLT,
+        (Comparison::LessThan, false) => IntPredicate::ULT,
+        (Comparison::LessOrEqual, true) => IntPredicate::SLE,
+        (Comparison::LessOrEqual, false) => IntPredicate::ULE,
+        (Comparison::GreaterThan, true) => IntPredicate::SGT,
+        (Comparison::GreaterThan, false) => IntPredicate::UGT,
+        (Comparison::GreaterOrEqual, true) => IntPredicate::SGE,
+        (Comparison::GreaterOrEqual, false) => IntPredicate::UGE,
+    }
+}
+
+fn float_predicate(comparison: FloatComparison) -> FloatPredicate {
+    match comparison {
+        FloatComparison::Ordered(Comparison::Equal) => FloatPredicate::OEQ,
+        FloatComparison::Ordered(Comparison::NotEqual) => FloatPredicate::ONE,
+        FloatComparison::Ordered(Comparison::LessThan) => FloatPredicate::OLT,
+        FloatComparison::Ordered(Comparison::LessOrEqual) => FloatPredicate::OLE,
+        FloatComparison::Ordered(Comparison::GreaterThan) => FloatPredicate::OGT,
+        FloatComparison::Ordered(Comparison::GreaterOrEqual) => FloatPredicate::OGE,
+        FloatComparison::Unordered(Comparison::Equal) => FloatPredicate::UEQ,
+        FloatComparison::Unordered(Comparison::NotEqual) => FloatPredicate::UNE,
+        FloatComparison::Unordered(Comparison::LessThan) => FloatPredicate::ULT,
+        FloatComparison::Unordered(Comparison::LessOrEqual) => FloatPredicate::ULE,
+        FloatComparison::Unordered(Comparison::GreaterThan) => FloatPredicate::UGT,
+        FloatComparison::Unordered(Comparison::GreaterOrEqual) => FloatPredicate::UGE,
+        FloatComparison::NotNaN => FloatPredicate::ORD,
+        FloatComparison::IsNaN => FloatPredicate::UNO,
+    }
 }
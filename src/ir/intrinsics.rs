@@ -0,0 +1,65 @@
+/// The shapes of the intrinsics lowerers may call, which decide the arguments
+/// they take and the overload of the intrinsic they resolve to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntrinsicKind {
+    /// `(iN) -> iN`
+    IntegerUnary,
+    /// `(iN) -> iN`, where zero gives the width of the integer
+    CountZeros,
+    /// `(fN) -> fN`
+    FloatUnary,
+    /// `(fN, fN) -> fN`
+    FloatBinary,
+    /// `(dest: ptr, source: ptr, bytes: iN) -> void`
+    MemoryTransfer,
+    /// `(dest: ptr, value: i8, bytes: iN) -> void`
+    MemorySet,
+}
+
+/// Every intrinsic available through the `intrinsic` instruction, by the name
+/// it's called with.
+const INTRINSICS: &[(&str, IntrinsicKind)] = &[
+    ("ctpop", IntrinsicKind::IntegerUnary),
+    ("bswap", IntrinsicKind::IntegerUnary),
+    ("bitreverse", IntrinsicKind::IntegerUnary),
+    ("ctlz", IntrinsicKind::CountZeros),
+    ("cttz", IntrinsicKind::CountZeros),
+    ("sqrt", IntrinsicKind::FloatUnary),
+    ("fabs", IntrinsicKind::FloatUnary),
+    ("floor", IntrinsicKind::FloatUnary),
+    ("ceil", IntrinsicKind::FloatUnary),
+    ("trunc", IntrinsicKind::FloatUnary),
+    ("round", IntrinsicKind::FloatUnary),
+    ("sin", IntrinsicKind::FloatUnary),
+    ("cos", IntrinsicKind::FloatUnary),
+    ("exp", IntrinsicKind::FloatUnary),
+    ("log", IntrinsicKind::FloatUnary),
+    ("pow", IntrinsicKind::FloatBinary),
+    ("minnum", IntrinsicKind::FloatBinary),
+    ("maxnum", IntrinsicKind::FloatBinary),
+    ("copysign", IntrinsicKind::FloatBinary),
+    ("memcpy", IntrinsicKind::MemoryTransfer),
+    ("memmove", IntrinsicKind::MemoryTransfer),
+    ("memset", IntrinsicKind::MemorySet),
+];
+
+impl IntrinsicKind {
+    /// Looks up an intrinsic by the name it's called with, such as `ctpop`.
+    pub fn parse(name: &str) -> Option<Self> {
+        INTRINSICS
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, kind)| *kind)
+    }
+
+    /// How many arguments intrinsics of this kind take.
+    pub fn arity(self) -> usize {
+        match self {
+            IntrinsicKind::IntegerUnary | IntrinsicKind::CountZeros | IntrinsicKind::FloatUnary => {
+                1
+            }
+            IntrinsicKind::FloatBinary => 2,
+            IntrinsicKind::MemoryTransfer | IntrinsicKind::MemorySet => 3,
+        }
+    }
+}
@@ -1,71 +1,460 @@
+use crate::ir::intrinsics::IntrinsicKind;
 use quick_js::JsValue;
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::RangeInclusive,
+    sync::{Arc, Mutex},
+};
 
 pub type JsMetaHandle = Arc<Mutex<JsMeta>>;
 
+/// The symbol of the C entry point, which calls the lowerer's main method.
+pub const ENTRY_SYMBOL: &str = "main";
+
 #[derive(Debug, Clone)]
 pub struct JsMeta {
     pub(crate) main_id: Option<MethodId>,
+    pub(crate) bool_type: TypeId,
     pub(crate) types: Vec<TypeDefinition>,
     pub(crate) methods: Vec<MethodDefinition>,
+    pub(crate) globals: Vec<GlobalDefinition>,
 }
 
 pub type TypeId = i32;
 pub type MethodId = i32;
 pub type BlockId = i32;
+pub type LocalId = i32;
+pub type GlobalId = i32;
 
 impl JsMeta {
-    pub fn add_type(&mut self, signed: bool, bits: i32) -> TypeId {
-        if bits < 0 {
-            panic!("expected >= 0 bits");
+    pub fn new() -> Self {
+        let mut meta = JsMeta {
+            main_id: None,
+            bool_type: 0,
+            types: vec![],
+            methods: vec![],
+            globals: vec![],
+        };
+
+        // comparisons produce a `bool`, so it must exist regardless of what the
+        // primer defines
+        meta.bool_type = meta.add_type(TypeDefinition::Integer {
+            signed: false,
+            bits: 1,
+        });
+        meta
+    }
+
+    /// Types are interned, so adding a type identical to an existing one
+    /// returns the id of the existing type.
+    pub fn add_type(&mut self, definition: TypeDefinition) -> TypeId {
+        if let Some(type_id) = self.find_type(&definition) {
+            return type_id;
         }
 
         let type_id = self.types.len();
-        self.types.push(TypeDefinition {
-            signed,
-            bits: bits as u32,
-        });
+        self.types.push(definition);
 
         type_id as TypeId
     }
 
+    /// Structs are interned like any other type, but as LLVM names them,
+    /// another struct can't share the name of one with different fields.
+    pub fn add_struct(&mut self, name: String, fields: Vec<TypeId>) -> Result<TypeId, String> {
+        // ensure the fields exist, and can hold a value
+        for field in fields.iter() {
+            if let TypeDefinition::Void = self.try_get_type(*field)? {
+                return Err(format!("fields of struct `{}` cannot be void", name));
+            }
+        }
+
+        let existing = self.types.iter().find_map(|definition| match definition {
+            TypeDefinition::Struct {
+                name: existing_name,
+                fields: existing_fields,
+            } if *existing_name == name && *existing_fields != fields => Some(existing_fields),
+            _ => None,
+        });
+
+        if let Some(existing_fields) = existing {
+            return Err(format!(
+                "struct `{}` is already defined with different fields ({})",
+                name,
+                existing_fields
+                    .iter()
+                    .map(|field| self.type_name(*field))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        // `field_ptr` produces pointers to the fields, so make sure their types
+        // exist
+        for field in fields.iter() {
+            self.add_type(TypeDefinition::Pointer { pointee: *field });
+        }
+
+        Ok(self.add_type(TypeDefinition::Struct { name, fields }))
+    }
+
     pub fn add_method(
         &mut self,
         name: String,
         return_type: TypeId,
         parameters: Vec<TypeId>,
-    ) -> MethodId {
-        // ensure that the types specified exist
-        self.get_type(return_type);
-        parameters
-            .iter()
-            .map(|id| self.get_type(*id))
-            .for_each(drop);
+    ) -> Result<MethodId, String> {
+        self.declare_method(MethodDefinition {
+            name,
+            return_type,
+            parameters,
+            locals: vec![],
+            blocks: vec![],
+            external: false,
+            variadic: false,
+            options: MethodOptions::default(),
+        })
+    }
 
-        let method_id = self.methods.len();
-        self.methods.push(MethodDefinition {
+    /// Declares a method defined outside of the module, such as one from the
+    /// C standard library. It keeps its name as-is, and has no body.
+    pub fn add_extern(
+        &mut self,
+        name: String,
+        return_type: TypeId,
+        parameters: Vec<TypeId>,
+        variadic: bool,
+    ) -> Result<MethodId, String> {
+        self.declare_method(MethodDefinition {
             name,
             return_type,
             parameters,
+            locals: vec![],
             blocks: vec![],
+            external: true,
+            variadic,
+            options: MethodOptions::default(),
+        })
+    }
+
+    fn declare_method(&mut self, method: MethodDefinition) -> Result<MethodId, String> {
+        // ensure that the types specified exist
+        self.try_get_type(method.return_type)?;
+        for parameter in method.parameters.iter() {
+            if let TypeDefinition::Void = self.try_get_type(*parameter)? {
+                return Err(format!(
+                    "parameters of method `{}` cannot be void",
+                    method.name
+                ));
+            }
+        }
+
+        let method_id = self.methods.len() as MethodId;
+        self.check_symbol(&method.symbol(), &format!("method `{}`", method.name), None)?;
+
+        // `fn_addr` produces a pointer to the method, so make sure its type
+        // exists
+        self.add_type(method.signature());
+        self.methods.push(method);
+
+        Ok(method_id)
+    }
+
+    /// Replaces the options of a method, which may change the symbol it's
+    /// linked as.
+    pub fn set_method_options(
+        &mut self,
+        method_id: MethodId,
+        options: MethodOptions,
+    ) -> Result<(), String> {
+        let mut method = self.get_method(method_id)?.clone();
+        method.options = options;
+        self.check_symbol(
+            &method.symbol(),
+            &format!("method `{}`", method.name),
+            Some(method_id),
+        )?;
+
+        self.methods[method_id as usize] = method;
+        Ok(())
+    }
+
+    /// Ensures that `what` can be linked as `symbol`, and that nothing else in
+    /// the module already is, as LLVM would quietly rename one of them. A
+    /// method being renamed is passed as `except`, so that it isn't compared
+    /// against itself.
+    fn check_symbol(
+        &self,
+        symbol: &str,
+        what: &str,
+        except: Option<MethodId>,
+    ) -> Result<(), String> {
+        if symbol.is_empty() || symbol.contains('\0') {
+            return Err(format!(
+                "{} cannot be linked as {:?}, as symbols must be non-empty and contain no nul bytes",
+                what, symbol
+            ));
+        }
+
+        if symbol == ENTRY_SYMBOL {
+            return Err(format!(
+                "{} cannot be linked as `{}`, which is reserved for the entry point of the program",
+                what, symbol
+            ));
+        }
+
+        if symbol.starts_with("llvm.") {
+            return Err(format!(
+                "{} cannot be linked as `{}`, as symbols starting with `llvm.` are reserved for intrinsics",
+                what, symbol
+            ));
+        }
+
+        let method = self
+            .methods
+            .iter()
+            .enumerate()
+            .filter(|(method_id, _)| Some(*method_id as MethodId) != except)
+            .find(|(_, method)| method.symbol() == symbol);
+
+        if let Some((_, method)) = method {
+            return Err(format!(
+                "{} would be linked as `{}`, which method `{}` already is",
+                what, symbol, method.name
+            ));
+        }
+
+        let global = self
+            .globals
+            .iter()
+            .find(|global| global.symbol().as_deref() == Some(symbol));
+
+        if let Some(global) = global {
+            return Err(format!(
+                "{} would be linked as `{}`, which global `{}` already is",
+                what, symbol, global.name
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn add_global(
+        &mut self,
+        name: String,
+        type_id: TypeId,
+        initializer: Option<Constant>,
+        mutable: bool,
+    ) -> Result<GlobalId, String> {
+        match (self.try_get_type(type_id)?, &initializer) {
+            (TypeDefinition::Void, _) => return Err(format!("global `{}` cannot be void", name)),
+            (TypeDefinition::Integer { signed, bits }, Some(constant)) => {
+                constant.warn_if_imprecise();
+
+                if let Err(message) = constant.to_words(*signed, *bits) {
+                    return Err(format!(
+                        "invalid initializer for global `{}`: {}",
+                        name, message
+                    ));
+                }
+            }
+            (TypeDefinition::Float { .. }, _) | (_, None) => {}
+            (_, Some(_)) => {
+                return Err(format!(
+                    "global `{}` can only be initialized with a constant if it is a number",
+                    name
+                ))
+            }
+        }
+
+        // globals share a namespace with methods, as both are linked by name
+        self.check_symbol(
+            &format!("tinkerlang_{}", name),
+            &format!("global `{}`", name),
+            None,
+        )?;
+
+        let pointer_type = self.add_type(TypeDefinition::Pointer { pointee: type_id });
+
+        let global_id = self.globals.len();
+        self.globals.push(GlobalDefinition {
+            name,
+            type_id,
+            pointer_type,
+            initializer: initializer.map_or(GlobalInitializer::Zero, GlobalInitializer::Constant),
+            mutable,
+        });
+
+        Ok(global_id as GlobalId)
+    }
+
+    /// Adds a private, immutable, nul terminated string. Its address is a
+    /// pointer to the first byte.
+    pub fn add_string(&mut self, value: String) -> GlobalId {
+        let mut bytes = value.into_bytes();
+        bytes.push(0);
+
+        let byte = self.add_type(TypeDefinition::Integer {
+            signed: false,
+            bits: 8,
+        });
+        let type_id = self.add_type(TypeDefinition::Array {
+            element: byte,
+            length: bytes.len() as u32,
+        });
+        let pointer_type = self.add_type(TypeDefinition::Pointer { pointee: byte });
+
+        let global_id = self.globals.len();
+        self.globals.push(GlobalDefinition {
+            name: "str".to_owned(),
+            type_id,
+            pointer_type,
+            initializer: GlobalInitializer::Bytes(bytes),
+            mutable: false,
         });
 
-        method_id as MethodId
+        global_id as GlobalId
     }
 
     pub fn get_type<'a>(&'a self, id: TypeId) -> &'a TypeDefinition {
         self.types.get(id as usize).expect("expected type")
     }
 
-    pub fn get_method_mut<'a>(&'a mut self, id: MethodId) -> &'a mut MethodDefinition {
-        self.methods.get_mut(id as usize).expect("expected type")
+    /// Gets a type that was given by the lowerer, which may not exist.
+    pub fn try_get_type<'a>(&'a self, id: TypeId) -> Result<&'a TypeDefinition, String> {
+        self.types
+            .get(id as usize)
+            .ok_or_else(|| format!("no type with id {}", id))
+    }
+
+    pub fn find_type(&self, definition: &TypeDefinition) -> Option<TypeId> {
+        self.types
+            .iter()
+            .position(|t| t == definition)
+            .map(|type_id| type_id as TypeId)
+    }
+
+    /// A readable name for a type, such as `i32`, `*u8` or `[f64; 4]`.
+    pub fn type_name(&self, id: TypeId) -> String {
+        match self.get_type(id) {
+            TypeDefinition::Integer {
+                signed: false,
+                bits: 1,
+            } => "bool".to_owned(),
+            TypeDefinition::Integer { signed, bits } => {
+                format!("{}{}", if *signed { "i" } else { "u" }, bits)
+            }
+            TypeDefinition::Float { bits } => format!("f{}", bits),
+            TypeDefinition::Void => "void".to_owned(),
+            TypeDefinition::Pointer { pointee } => format!("*{}", self.type_name(*pointee)),
+            TypeDefinition::Struct { name, .. } => format!("struct {}", name),
+            TypeDefinition::Array { element, length } => {
+                format!("[{}; {}]", self.type_name(*element), length)
+            }
+            TypeDefinition::FunctionPointer {
+                return_type,
+                parameters,
+                variadic,
+            } => {
+                let mut parameters = parameters
+                    .iter()
+                    .map(|parameter| self.type_name(*parameter))
+                    .collect::<Vec<_>>();
+
+                if *variadic {
+                    parameters.push("...".to_owned());
+                }
+
+                format!(
+                    "fn({}) -> {}",
+                    parameters.join(", "),
+                    self.type_name(*return_type)
+                )
+            }
+        }
+    }
+
+    pub fn get_method<'a>(&'a self, id: MethodId) -> Result<&'a MethodDefinition, String> {
+        self.methods
+            .get(id as usize)
+            .ok_or_else(|| format!("no method with id {}", id))
+    }
+
+    pub fn get_method_mut<'a>(
+        &'a mut self,
+        id: MethodId,
+    ) -> Result<&'a mut MethodDefinition, String> {
+        self.methods
+            .get_mut(id as usize)
+            .ok_or_else(|| format!("no method with id {}", id))
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct TypeDefinition {
-    pub(crate) signed: bool,
-    pub(crate) bits: u32,
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeDefinition {
+    Integer {
+        signed: bool,
+        bits: u32,
+    },
+    Float {
+        bits: u32,
+    },
+    Void,
+    /// A pointer to a value of the given type. Pointers to `void` are
+    /// pointers to bytes.
+    Pointer {
+        pointee: TypeId,
+    },
+    /// A named record of fields, laid out in order.
+    Struct {
+        name: String,
+        fields: Vec<TypeId>,
+    },
+    /// A fixed number of values of the same type, laid out contiguously.
+    Array {
+        element: TypeId,
+        length: u32,
+    },
+    /// A pointer to a method with the given signature.
+    FunctionPointer {
+        return_type: TypeId,
+        parameters: Vec<TypeId>,
+        variadic: bool,
+    },
+}
+
+impl TypeDefinition {
+    pub fn is_signed(&self) -> bool {
+        matches!(self, TypeDefinition::Integer { signed: true, .. })
+    }
+
+    pub fn bits(&self) -> u32 {
+        match self {
+            TypeDefinition::Integer { bits, .. } | TypeDefinition::Float { bits } => *bits,
+            TypeDefinition::Void
+            | TypeDefinition::Pointer { .. }
+            | TypeDefinition::Struct { .. }
+            | TypeDefinition::Array { .. }
+            | TypeDefinition::FunctionPointer { .. } => 0,
+        }
+    }
+
+    pub fn pointee(&self) -> Option<TypeId> {
+        match self {
+            TypeDefinition::Pointer { pointee } => Some(*pointee),
+            _ => None,
+        }
+    }
+
+    /// The type of the field at `index`, if this is an aggregate that has
+    /// such a field.
+    pub fn field(&self, index: u32) -> Option<TypeId> {
+        match self {
+            TypeDefinition::Struct { fields, .. } => fields.get(index as usize).copied(),
+            TypeDefinition::Array { element, length } if index < *length => Some(*element),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -73,22 +462,282 @@ pub struct MethodDefinition {
     pub(crate) name: String,
     pub(crate) return_type: TypeId,
     pub(crate) parameters: Vec<TypeId>,
+    pub(crate) locals: Vec<TypeId>,
     pub(crate) blocks: Vec<BlockDefinition>,
+    /// Whether the method is declared here, but defined elsewhere.
+    pub(crate) external: bool,
+    /// Whether the method accepts more parameters than it declares.
+    pub(crate) variadic: bool,
+    pub(crate) options: MethodOptions,
 }
 
 impl MethodDefinition {
-    pub fn add_block(&mut self, name: String) -> BlockId {
+    /// The type of a pointer to this method.
+    pub fn signature(&self) -> TypeDefinition {
+        TypeDefinition::FunctionPointer {
+            return_type: self.return_type,
+            parameters: self.parameters.clone(),
+            variadic: self.variadic,
+        }
+    }
+
+    /// The name the method is linked as. Methods defined in the module are
+    /// prefixed, unless they're given an export name, and external methods
+    /// keep their real name.
+    pub fn symbol(&self) -> String {
+        match &self.options.export_name {
+            _ if self.external => self.name.clone(),
+            Some(export_name) => export_name.clone(),
+            None => format!("tinkerlang_{}", self.name),
+        }
+    }
+
+    pub fn add_local(&mut self, type_id: TypeId) -> LocalId {
+        let local_id = self.locals.len();
+        self.locals.push(type_id);
+
+        local_id as LocalId
+    }
+
+    pub fn add_block(&mut self, name: String) -> Result<BlockId, String> {
+        if self.external {
+            return Err(format!(
+                "external method `{}` cannot have blocks",
+                self.name
+            ));
+        }
+
         let block_id = self.blocks.len();
         self.blocks.push(BlockDefinition {
             name,
             instructions: vec![],
         });
 
-        block_id as BlockId
+        Ok(block_id as BlockId)
+    }
+
+    pub fn get_block_mut<'a>(&'a mut self, id: BlockId) -> Result<&'a mut BlockDefinition, String> {
+        let name = &self.name;
+        self.blocks
+            .get_mut(id as usize)
+            .ok_or_else(|| format!("method `{}` has no block with id {}", name, id))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlobalDefinition {
+    pub(crate) name: String,
+    pub(crate) type_id: TypeId,
+    /// The type of the global's address, which is what `global_addr`
+    /// produces.
+    pub(crate) pointer_type: TypeId,
+    pub(crate) initializer: GlobalInitializer,
+    pub(crate) mutable: bool,
+}
+
+impl GlobalDefinition {
+    /// Literals are only reachable through `global_addr`, so they're kept
+    /// private to the module.
+    pub fn is_literal(&self) -> bool {
+        matches!(self.initializer, GlobalInitializer::Bytes(_))
+    }
+
+    /// The name the global is linked as. Literals are left unnamed, so they
+    /// can never clash with a symbol.
+    pub fn symbol(&self) -> Option<String> {
+        if self.is_literal() {
+            None
+        } else {
+            Some(format!("tinkerlang_{}", self.name))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum GlobalInitializer {
+    /// Every bit of the global is zero.
+    Zero,
+    Constant(Constant),
+    /// The bytes of a string literal, including its nul terminator.
+    Bytes(Vec<u8>),
+}
+
+/// How a method is exposed to the linker, and hints for generating its code.
+#[derive(Debug, Clone, Default)]
+pub struct MethodOptions {
+    pub(crate) linkage: MethodLinkage,
+    /// The symbol the method is exported as, instead of its prefixed name.
+    pub(crate) export_name: Option<String>,
+    pub(crate) calling_convention: CallingConvention,
+    pub(crate) attributes: Vec<MethodAttribute>,
+}
+
+impl MethodOptions {
+    /// Parses an options object from the primer, such as
+    /// `{ linkage: "internal", noinline: true }`.
+    pub fn parse(method_name: &str, options: JsValue) -> Result<Self, String> {
+        let options = match options {
+            JsValue::Object(options) => options,
+            JsValue::Null | JsValue::Undefined => HashMap::new(),
+            _ => {
+                return Err(format!(
+                    "options of method `{}` must be an object, not {:?}",
+                    method_name, options
+                ))
+            }
+        };
+
+        let mut parsed = MethodOptions::default();
+
+        for (key, value) in options.into_iter() {
+            // `null` and `undefined` leave an option as its default
+            let text = |value: JsValue| match value {
+                JsValue::String(text) => Ok(Some(text)),
+                JsValue::Null | JsValue::Undefined => Ok(None),
+                _ => Err(format!(
+                    "option `{}` of method `{}` must be a string, not {:?}",
+                    key, method_name, value
+                )),
+            };
+
+            match key.as_str() {
+                "linkage" => {
+                    let linkage = match text(value)? {
+                        Some(linkage) => linkage,
+                        None => continue,
+                    };
+                    parsed.linkage = MethodLinkage::parse(linkage.as_str()).ok_or_else(|| {
+                        format!(
+                            "unknown linkage `{}` for method `{}`, expected internal, external or weak",
+                            linkage, method_name
+                        )
+                    })?;
+                }
+                "exportName" => parsed.export_name = text(value)?,
+                "callingConvention" => {
+                    let convention = match text(value)? {
+                        Some(convention) => convention,
+                        None => continue,
+                    };
+                    parsed.calling_convention = CallingConvention::parse(convention.as_str())
+                        .ok_or_else(|| {
+                            format!(
+                                "unknown calling convention `{}` for method `{}`, expected c, fast or cold",
+                                convention, method_name
+                            )
+                        })?;
+                }
+                name => {
+                    let attribute = MethodAttribute::parse(name).ok_or_else(|| {
+                        format!("unknown option `{}` for method `{}`", name, method_name)
+                    })?;
+
+                    match value {
+                        JsValue::Bool(true) => parsed.attributes.push(attribute),
+                        JsValue::Bool(false) | JsValue::Null | JsValue::Undefined => {}
+                        value => {
+                            return Err(format!(
+                                "option `{}` of method `{}` must be a boolean, not {:?}",
+                                name, method_name, value
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        if parsed.attributes.contains(&MethodAttribute::NoInline)
+            && parsed.attributes.contains(&MethodAttribute::AlwaysInline)
+        {
+            return Err(format!(
+                "method `{}` cannot be both noinline and alwaysinline",
+                method_name
+            ));
+        }
+
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodLinkage {
+    /// Visible to other modules.
+    External,
+    /// Only visible within the module.
+    Internal,
+    /// Visible to other modules, but may be replaced by another definition
+    /// with the same name.
+    Weak,
+}
+
+impl Default for MethodLinkage {
+    fn default() -> Self {
+        MethodLinkage::External
+    }
+}
+
+impl MethodLinkage {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "external" => Some(MethodLinkage::External),
+            "internal" => Some(MethodLinkage::Internal),
+            "weak" => Some(MethodLinkage::Weak),
+            _ => None,
+        }
     }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallingConvention {
+    C,
+    Fast,
+    Cold,
+}
+
+impl Default for CallingConvention {
+    fn default() -> Self {
+        CallingConvention::C
+    }
+}
+
+impl CallingConvention {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "c" => Some(CallingConvention::C),
+            "fast" => Some(CallingConvention::Fast),
+            "cold" => Some(CallingConvention::Cold),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodAttribute {
+    NoInline,
+    AlwaysInline,
+    NoReturn,
+    Cold,
+}
 
-    pub fn get_block_mut<'a>(&'a mut self, id: BlockId) -> &'a mut BlockDefinition {
-        self.blocks.get_mut(id as usize).expect("expected block")
+impl MethodAttribute {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "noinline" => Some(MethodAttribute::NoInline),
+            "alwaysinline" => Some(MethodAttribute::AlwaysInline),
+            "noreturn" => Some(MethodAttribute::NoReturn),
+            "cold" => Some(MethodAttribute::Cold),
+            _ => None,
+        }
+    }
+
+    /// The name LLVM knows the attribute by.
+    pub fn name(self) -> &'static str {
+        match self {
+            MethodAttribute::NoInline => "noinline",
+            MethodAttribute::AlwaysInline => "alwaysinline",
+            MethodAttribute::NoReturn => "noreturn",
+            MethodAttribute::Cold => "cold",
+        }
     }
 }
 
@@ -98,7 +747,7 @@ pub struct BlockDefinition {
     pub(crate) instructions: Vec<Instruction>,
 }
 
-type Register = i32;
+pub type Register = i32;
 
 #[derive(Debug, Clone)]
 pub enum Instruction {
@@ -107,6 +756,104 @@ pub enum Instruction {
         a: Register,
         b: Register,
     },
+    Sub {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    Mul {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    Div {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    Rem {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    Neg {
+        result: Register,
+        source: Register,
+    },
+    And {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    Or {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    Xor {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    ShiftLeft {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    ShiftRight {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    Not {
+        result: Register,
+        source: Register,
+    },
+    FAdd {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    FSub {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    FMul {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    FDiv {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    FRem {
+        result: Register,
+        a: Register,
+        b: Register,
+    },
+    FNeg {
+        result: Register,
+        source: Register,
+    },
+    FloatCompare {
+        result: Register,
+        comparison: FloatComparison,
+        a: Register,
+        b: Register,
+    },
+    IntToFloat {
+        result: Register,
+        source: Register,
+        convert_into: TypeId,
+    },
+    FloatToInt {
+        result: Register,
+        source: Register,
+        convert_into: TypeId,
+    },
     LoadParameter {
         result: Register,
         parameter_number: i32,
@@ -117,7 +864,7 @@ pub enum Instruction {
         constant: Constant,
     },
     Call {
-        result: Register,
+        result: Option<Register>,
         method_id: MethodId,
         parameters: Vec<Register>,
     },
@@ -126,185 +873,1969 @@ pub enum Instruction {
         source: Register,
         truncate_into: TypeId,
     },
-    Return {
-        result: Option<Register>,
-    },
+    SignExtend {
+        result: Register,
+        source: Register,
+        extend_into: TypeId,
+    },
+    ZeroExtend {
+        result: Register,
+        source: Register,
+        extend_into: TypeId,
+    },
+    Cast {
+        result: Register,
+        source: Register,
+        cast_into: TypeId,
+    },
+    Bitcast {
+        result: Register,
+        source: Register,
+        cast_into: TypeId,
+    },
+    Return {
+        result: Option<Register>,
+    },
+    Compare {
+        result: Register,
+        comparison: Comparison,
+        a: Register,
+        b: Register,
+    },
+    Branch {
+        target: BlockId,
+    },
+    ConditionalBranch {
+        condition: Register,
+        then_block: BlockId,
+        else_block: BlockId,
+    },
+    Phi {
+        result: Register,
+        type_id: TypeId,
+        incoming: Vec<(Register, BlockId)>,
+    },
+    LoadLocal {
+        result: Register,
+        local_id: LocalId,
+    },
+    StoreLocal {
+        local_id: LocalId,
+        source: Register,
+    },
+    AddressOf {
+        result: Register,
+        local_id: LocalId,
+    },
+    LoadPointer {
+        result: Register,
+        pointer: Register,
+    },
+    StorePointer {
+        pointer: Register,
+        source: Register,
+    },
+    Offset {
+        result: Register,
+        pointer: Register,
+        index: Register,
+    },
+    PointerToInt {
+        result: Register,
+        source: Register,
+        convert_into: TypeId,
+    },
+    IntToPointer {
+        result: Register,
+        source: Register,
+        convert_into: TypeId,
+    },
+    LoadZero {
+        result: Register,
+        type_id: TypeId,
+    },
+    ExtractField {
+        result: Register,
+        aggregate: Register,
+        index: u32,
+    },
+    InsertField {
+        result: Register,
+        aggregate: Register,
+        index: u32,
+        source: Register,
+    },
+    FieldPointer {
+        result: Register,
+        pointer: Register,
+        index: u32,
+    },
+    GlobalAddress {
+        result: Register,
+        global_id: GlobalId,
+    },
+    MethodAddress {
+        result: Register,
+        method_id: MethodId,
+    },
+    CallIndirect {
+        result: Option<Register>,
+        pointer: Register,
+        parameters: Vec<Register>,
+    },
+    Switch {
+        condition: Register,
+        default: BlockId,
+        cases: Vec<(Constant, BlockId)>,
+    },
+    Select {
+        result: Register,
+        condition: Register,
+        a: Register,
+        b: Register,
+    },
+    Unreachable,
+    /// Adds `a` and `b`, also writing whether the result overflowed into
+    /// `overflow`.
+    AddChecked {
+        result: Register,
+        overflow: Register,
+        a: Register,
+        b: Register,
+    },
+    SubChecked {
+        result: Register,
+        overflow: Register,
+        a: Register,
+        b: Register,
+    },
+    MulChecked {
+        result: Register,
+        overflow: Register,
+        a: Register,
+        b: Register,
+    },
+    Trap,
+    /// Calls an LLVM intrinsic by its short name, such as `ctpop`. Which
+    /// intrinsics are available is decided when hydrating.
+    Intrinsic {
+        result: Option<Register>,
+        name: String,
+        arguments: Vec<Register>,
+    },
+    AtomicLoad {
+        result: Register,
+        pointer: Register,
+        ordering: MemoryOrdering,
+    },
+    AtomicStore {
+        pointer: Register,
+        source: Register,
+        ordering: MemoryOrdering,
+    },
+    /// Atomically replaces the value `pointer` points to with the result of
+    /// `operation` on it and `source`, writing the old value into `result`.
+    AtomicRmw {
+        result: Register,
+        operation: AtomicOperation,
+        pointer: Register,
+        source: Register,
+        ordering: MemoryOrdering,
+    },
+    /// Atomically replaces the value `pointer` points to with `replacement`
+    /// if it equals `expected`. The old value is written into `result`, and
+    /// whether it was replaced into `success`.
+    CompareExchange {
+        result: Register,
+        success: Register,
+        pointer: Register,
+        expected: Register,
+        replacement: Register,
+        success_ordering: MemoryOrdering,
+        failure_ordering: MemoryOrdering,
+    },
+    Fence {
+        ordering: MemoryOrdering,
+    },
 }
 
 impl Instruction {
-    pub fn deserialize<F: Fn(TypeId) -> bool>(
-        parameters: usize,
-        is_valid: F,
+    /// Deserializes an instruction emitted into `method`, checking what can
+    /// be checked before the rest of the method exists.
+    pub fn deserialize(
+        meta: &JsMeta,
+        method: &MethodDefinition,
         name: String,
         args: Vec<JsValue>,
-    ) -> Instruction {
+    ) -> Result<Instruction, String> {
         println!("DES: {:?} {:?}", name, args);
 
-        match name.as_str() {
+        Instruction::des_instruction(meta, method, name.as_str(), args)
+            .map_err(|message| format!("invalid `{}`: {}", name, message))
+    }
+
+    fn des_instruction(
+        meta: &JsMeta,
+        method: &MethodDefinition,
+        name: &str,
+        args: Vec<JsValue>,
+    ) -> Result<Instruction, String> {
+        let globals = meta.globals.len();
+        let parameters = method.parameters.len();
+        let blocks = method.blocks.len();
+        let locals = method.locals.len();
+
+        // the arguments are popped off the end by each instruction, so there
+        // must be exactly as many as it expects
+        let arity =
+            Instruction::arity(name).ok_or_else(|| format!("unrecognized instruction {}", name))?;
+
+        if !arity.contains(&args.len()) {
+            return Err(format!(
+                "expected {} arguments, but was given {}",
+                if arity.start() == arity.end() {
+                    arity.start().to_string()
+                } else {
+                    format!("{} to {}", arity.start(), arity.end())
+                },
+                args.len()
+            ));
+        }
+
+        match name {
             "add" => Instruction::des_add(args),
+            "sub" => Instruction::des_sub(args),
+            "mul" => Instruction::des_mul(args),
+            "div" => Instruction::des_div(args),
+            "rem" => Instruction::des_rem(args),
+            "neg" => Instruction::des_neg(args),
+            "and" => Instruction::des_and(args),
+            "or" => Instruction::des_or(args),
+            "xor" => Instruction::des_xor(args),
+            "not" => Instruction::des_not(args),
+            "shl" => Instruction::des_shl(args),
+            "shr" => Instruction::des_shr(args),
+            "fadd" => Instruction::des_fadd(args),
+            "fsub" => Instruction::des_fsub(args),
+            "fmul" => Instruction::des_fmul(args),
+            "fdiv" => Instruction::des_fdiv(args),
+            "frem" => Instruction::des_frem(args),
+            "fneg" => Instruction::des_fneg(args),
+            "fcmp" => Instruction::des_fcmp(args),
+            "itof" => Instruction::des_itof(args),
+            "ftoi" => Instruction::des_ftoi(args),
             "ld_param" => Instruction::des_ld_param(parameters, args),
-            "ld_const" => Instruction::des_ld_const(is_valid, args),
-            "call" => Instruction::des_call(args),
+            "ld_const" => Instruction::des_ld_const(meta, args),
+            "call" => Instruction::des_call(meta, args),
             "ret" => Instruction::des_ret(args),
             "trunc" => Instruction::des_trunc(args),
-            _ => panic!("unrecognized instruction {}", name),
+            "sext" => Instruction::des_sext(args),
+            "zext" => Instruction::des_zext(args),
+            "cast" => Instruction::des_cast(args),
+            "bitcast" => Instruction::des_bitcast(args),
+            "eq" => Instruction::des_compare(Comparison::Equal, args),
+            "ne" => Instruction::des_compare(Comparison::NotEqual, args),
+            "lt" => Instruction::des_compare(Comparison::LessThan, args),
+            "le" => Instruction::des_compare(Comparison::LessOrEqual, args),
+            "gt" => Instruction::des_compare(Comparison::GreaterThan, args),
+            "ge" => Instruction::des_compare(Comparison::GreaterOrEqual, args),
+            "br" => Instruction::des_br(blocks, args),
+            "cond_br" => Instruction::des_cond_br(blocks, args),
+            "phi" => Instruction::des_phi(meta, blocks, args),
+            "load" => Instruction::des_load(locals, args),
+            "store" => Instruction::des_store(locals, args),
+            "addr_of" => Instruction::des_addr_of(locals, args),
+            "load_ptr" => Instruction::des_load_ptr(args),
+            "store_ptr" => Instruction::des_store_ptr(args),
+            "gep" => Instruction::des_gep(args),
+            "ptrtoint" => Instruction::des_ptrtoint(args),
+            "inttoptr" => Instruction::des_inttoptr(args),
+            "ld_zero" => Instruction::des_ld_zero(meta, args),
+            "extract_field" => Instruction::des_extract_field(args),
+            "insert_field" => Instruction::des_insert_field(args),
+            "field_ptr" => Instruction::des_field_ptr(args),
+            "global_addr" => Instruction::des_global_addr(globals, args),
+            "fn_addr" => Instruction::des_fn_addr(meta, args),
+            "call_indirect" => Instruction::des_call_indirect(args),
+            "switch" => Instruction::des_switch(blocks, args),
+            "select" => Instruction::des_select(args),
+            "unreachable" => Ok(Instruction::Unreachable),
+            "add_checked" => Instruction::des_add_checked(args),
+            "sub_checked" => Instruction::des_sub_checked(args),
+            "mul_checked" => Instruction::des_mul_checked(args),
+            "trap" => Ok(Instruction::Trap),
+            "intrinsic" => Instruction::des_intrinsic(args),
+            "atomic_load" => Instruction::des_atomic_load(args),
+            "atomic_store" => Instruction::des_atomic_store(args),
+            "atomic_rmw" => Instruction::des_atomic_rmw(args),
+            "cmpxchg" => Instruction::des_cmpxchg(args),
+            "fence" => Instruction::des_fence(args),
+            _ => Err(format!("unrecognized instruction {}", name)),
+        }
+    }
+
+    /// How many arguments the primer emits the instruction with.
+    fn arity(name: &str) -> Option<RangeInclusive<usize>> {
+        let arity = match name {
+            "unreachable" | "trap" => 0..=0,
+            "ret" => 0..=1,
+            "br" | "fence" => 1..=1,
+            "neg" | "not" | "fneg" | "ld_param" | "fn_addr" | "load" | "store" | "addr_of"
+            | "load_ptr" | "store_ptr" | "ld_zero" | "global_addr" => 2..=2,
+            "add" | "sub" | "mul" | "div" | "rem" | "and" | "or" | "xor" | "shl" | "shr"
+            | "fadd" | "fsub" | "fmul" | "fdiv" | "frem" | "itof" | "ftoi" | "ld_const"
+            | "call" | "trunc" | "sext" | "zext" | "cast" | "bitcast" | "eq" | "ne" | "lt"
+            | "le" | "gt" | "ge" | "cond_br" | "phi" | "gep" | "ptrtoint" | "inttoptr"
+            | "extract_field" | "field_ptr" | "call_indirect" | "switch" | "intrinsic"
+            | "atomic_load" | "atomic_store" => 3..=3,
+            "fcmp" | "select" | "insert_field" | "add_checked" | "sub_checked" | "mul_checked" => {
+                4..=4
+            }
+            "atomic_rmw" => 5..=5,
+            "cmpxchg" => 7..=7,
+            _ => return None,
+        };
+
+        Some(arity)
+    }
+
+    /// The name of the instruction, as it is emitted by the primer.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Instruction::Add { .. } => "add",
+            Instruction::Sub { .. } => "sub",
+            Instruction::Mul { .. } => "mul",
+            Instruction::Div { .. } => "div",
+            Instruction::Rem { .. } => "rem",
+            Instruction::Neg { .. } => "neg",
+            Instruction::And { .. } => "and",
+            Instruction::Or { .. } => "or",
+            Instruction::Xor { .. } => "xor",
+            Instruction::ShiftLeft { .. } => "shl",
+            Instruction::ShiftRight { .. } => "shr",
+            Instruction::Not { .. } => "not",
+            Instruction::FAdd { .. } => "fadd",
+            Instruction::FSub { .. } => "fsub",
+            Instruction::FMul { .. } => "fmul",
+            Instruction::FDiv { .. } => "fdiv",
+            Instruction::FRem { .. } => "frem",
+            Instruction::FNeg { .. } => "fneg",
+            Instruction::FloatCompare { .. } => "fcmp",
+            Instruction::IntToFloat { .. } => "itof",
+            Instruction::FloatToInt { .. } => "ftoi",
+            Instruction::LoadParameter { .. } => "ld_param",
+            Instruction::LoadConstant { .. } => "ld_const",
+            Instruction::Call { .. } => "call",
+            Instruction::Return { .. } => "ret",
+            Instruction::Truncate { .. } => "trunc",
+            Instruction::SignExtend { .. } => "sext",
+            Instruction::ZeroExtend { .. } => "zext",
+            Instruction::Cast { .. } => "cast",
+            Instruction::Bitcast { .. } => "bitcast",
+            Instruction::Compare { comparison, .. } => match comparison {
+                Comparison::Equal => "eq",
+                Comparison::NotEqual => "ne",
+                Comparison::LessThan => "lt",
+                Comparison::LessOrEqual => "le",
+                Comparison::GreaterThan => "gt",
+                Comparison::GreaterOrEqual => "ge",
+            },
+            Instruction::Branch { .. } => "br",
+            Instruction::ConditionalBranch { .. } => "cond_br",
+            Instruction::Phi { .. } => "phi",
+            Instruction::LoadLocal { .. } => "load",
+            Instruction::StoreLocal { .. } => "store",
+            Instruction::AddressOf { .. } => "addr_of",
+            Instruction::LoadPointer { .. } => "load_ptr",
+            Instruction::StorePointer { .. } => "store_ptr",
+            Instruction::Offset { .. } => "gep",
+            Instruction::PointerToInt { .. } => "ptrtoint",
+            Instruction::IntToPointer { .. } => "inttoptr",
+            Instruction::LoadZero { .. } => "ld_zero",
+            Instruction::ExtractField { .. } => "extract_field",
+            Instruction::InsertField { .. } => "insert_field",
+            Instruction::FieldPointer { .. } => "field_ptr",
+            Instruction::GlobalAddress { .. } => "global_addr",
+            Instruction::MethodAddress { .. } => "fn_addr",
+            Instruction::CallIndirect { .. } => "call_indirect",
+            Instruction::Switch { .. } => "switch",
+            Instruction::Select { .. } => "select",
+            Instruction::Unreachable => "unreachable",
+            Instruction::AddChecked { .. } => "add_checked",
+            Instruction::SubChecked { .. } => "sub_checked",
+            Instruction::MulChecked { .. } => "mul_checked",
+            Instruction::Trap => "trap",
+            Instruction::Intrinsic { .. } => "intrinsic",
+            Instruction::AtomicLoad { .. } => "atomic_load",
+            Instruction::AtomicStore { .. } => "atomic_store",
+            Instruction::AtomicRmw { .. } => "atomic_rmw",
+            Instruction::CompareExchange { .. } => "cmpxchg",
+            Instruction::Fence { .. } => "fence",
+        }
+    }
+
+    /// The register this instruction writes to, if any. Checked arithmetic
+    /// also writes to an `overflow` register, which isn't included.
+    pub fn result(&self) -> Option<Register> {
+        match self {
+            Instruction::AddChecked { result, .. }
+            | Instruction::SubChecked { result, .. }
+            | Instruction::MulChecked { result, .. }
+            | Instruction::AtomicLoad { result, .. }
+            | Instruction::AtomicRmw { result, .. }
+            | Instruction::CompareExchange { result, .. } => Some(*result),
+            Instruction::Add { result, .. }
+            | Instruction::Sub { result, .. }
+            | Instruction::Mul { result, .. }
+            | Instruction::Div { result, .. }
+            | Instruction::Rem { result, .. }
+            | Instruction::Neg { result, .. }
+            | Instruction::And { result, .. }
+            | Instruction::Or { result, .. }
+            | Instruction::Xor { result, .. }
+            | Instruction::Not { result, .. }
+            | Instruction::ShiftLeft { result, .. }
+            | Instruction::ShiftRight { result, .. }
+            | Instruction::FAdd { result, .. }
+            | Instruction::FSub { result, .. }
+            | Instruction::FMul { result, .. }
+            | Instruction::FDiv { result, .. }
+            | Instruction::FRem { result, .. }
+            | Instruction::FNeg { result, .. }
+            | Instruction::FloatCompare { result, .. }
+            | Instruction::IntToFloat { result, .. }
+            | Instruction::FloatToInt { result, .. }
+            | Instruction::LoadParameter { result, .. }
+            | Instruction::LoadConstant { result, .. }
+            | Instruction::Truncate { result, .. }
+            | Instruction::SignExtend { result, .. }
+            | Instruction::ZeroExtend { result, .. }
+            | Instruction::Cast { result, .. }
+            | Instruction::Bitcast { result, .. }
+            | Instruction::Compare { result, .. }
+            | Instruction::Phi { result, .. }
+            | Instruction::LoadLocal { result, .. }
+            | Instruction::AddressOf { result, .. }
+            | Instruction::LoadPointer { result, .. }
+            | Instruction::Offset { result, .. }
+            | Instruction::PointerToInt { result, .. }
+            | Instruction::IntToPointer { result, .. }
+            | Instruction::LoadZero { result, .. }
+            | Instruction::ExtractField { result, .. }
+            | Instruction::InsertField { result, .. }
+            | Instruction::FieldPointer { result, .. }
+            | Instruction::GlobalAddress { result, .. }
+            | Instruction::MethodAddress { result, .. }
+            | Instruction::Select { result, .. } => Some(*result),
+            Instruction::Call { result, .. }
+            | Instruction::CallIndirect { result, .. }
+            | Instruction::Intrinsic { result, .. } => *result,
+            Instruction::Return { .. }
+            | Instruction::StoreLocal { .. }
+            | Instruction::StorePointer { .. }
+            | Instruction::Branch { .. }
+            | Instruction::ConditionalBranch { .. }
+            | Instruction::Switch { .. }
+            | Instruction::Unreachable
+            | Instruction::Trap
+            | Instruction::AtomicStore { .. }
+            | Instruction::Fence { .. } => None,
+        }
+    }
+
+    /// Every register this instruction writes to.
+    pub fn results(&self) -> Vec<Register> {
+        match self {
+            Instruction::AddChecked {
+                result, overflow, ..
+            }
+            | Instruction::SubChecked {
+                result, overflow, ..
+            }
+            | Instruction::MulChecked {
+                result, overflow, ..
+            } => vec![*result, *overflow],
+            Instruction::CompareExchange {
+                result, success, ..
+            } => vec![*result, *success],
+            _ => self.result().into_iter().collect(),
+        }
+    }
+
+    /// The registers this instruction reads from. The incoming values of a
+    /// `phi` are not included, as they only need to be available at the end of
+    /// the block they flow in from.
+    pub fn operands(&self) -> Vec<Register> {
+        match self {
+            Instruction::Add { a, b, .. }
+            | Instruction::Sub { a, b, .. }
+            | Instruction::Mul { a, b, .. }
+            | Instruction::Div { a, b, .. }
+            | Instruction::Rem { a, b, .. }
+            | Instruction::Compare { a, b, .. }
+            | Instruction::And { a, b, .. }
+            | Instruction::Or { a, b, .. }
+            | Instruction::Xor { a, b, .. }
+            | Instruction::ShiftLeft { a, b, .. }
+            | Instruction::ShiftRight { a, b, .. }
+            | Instruction::FAdd { a, b, .. }
+            | Instruction::FSub { a, b, .. }
+            | Instruction::FMul { a, b, .. }
+            | Instruction::FDiv { a, b, .. }
+            | Instruction::FRem { a, b, .. }
+            | Instruction::FloatCompare { a, b, .. }
+            | Instruction::AddChecked { a, b, .. }
+            | Instruction::SubChecked { a, b, .. }
+            | Instruction::MulChecked { a, b, .. } => vec![*a, *b],
+            Instruction::Neg { source, .. }
+            | Instruction::FNeg { source, .. }
+            | Instruction::Not { source, .. }
+            | Instruction::IntToFloat { source, .. }
+            | Instruction::FloatToInt { source, .. }
+            | Instruction::Truncate { source, .. }
+            | Instruction::SignExtend { source, .. }
+            | Instruction::ZeroExtend { source, .. }
+            | Instruction::Cast { source, .. }
+            | Instruction::Bitcast { source, .. } => vec![*source],
+            Instruction::Call { parameters, .. } => parameters.clone(),
+            Instruction::Intrinsic { arguments, .. } => arguments.clone(),
+            Instruction::AtomicLoad { pointer, .. } => vec![*pointer],
+            Instruction::AtomicStore {
+                pointer, source, ..
+            }
+            | Instruction::AtomicRmw {
+                pointer, source, ..
+            } => vec![*pointer, *source],
+            Instruction::CompareExchange {
+                pointer,
+                expected,
+                replacement,
+                ..
+            } => vec![*pointer, *expected, *replacement],
+            Instruction::CallIndirect {
+                pointer,
+                parameters,
+                ..
+            } => std::iter::once(*pointer)
+                .chain(parameters.iter().copied())
+                .collect(),
+            Instruction::Return { result } => result.iter().copied().collect(),
+            Instruction::ConditionalBranch { condition, .. }
+            | Instruction::Switch { condition, .. } => vec![*condition],
+            Instruction::Select {
+                condition, a, b, ..
+            } => vec![*condition, *a, *b],
+            Instruction::StoreLocal { source, .. } => vec![*source],
+            Instruction::LoadPointer { pointer, .. } => vec![*pointer],
+            Instruction::StorePointer { pointer, source } => vec![*pointer, *source],
+            Instruction::Offset { pointer, index, .. } => vec![*pointer, *index],
+            Instruction::PointerToInt { source, .. } | Instruction::IntToPointer { source, .. } => {
+                vec![*source]
+            }
+            Instruction::ExtractField { aggregate, .. } => vec![*aggregate],
+            Instruction::InsertField {
+                aggregate, source, ..
+            } => vec![*aggregate, *source],
+            Instruction::FieldPointer { pointer, .. } => vec![*pointer],
+            Instruction::LoadParameter { .. }
+            | Instruction::LoadConstant { .. }
+            | Instruction::LoadZero { .. }
+            | Instruction::GlobalAddress { .. }
+            | Instruction::MethodAddress { .. }
+            | Instruction::Unreachable
+            | Instruction::Trap
+            | Instruction::Fence { .. }
+            | Instruction::Branch { .. }
+            | Instruction::Phi { .. }
+            | Instruction::LoadLocal { .. }
+            | Instruction::AddressOf { .. } => vec![],
+        }
+    }
+
+    /// The blocks control may be transferred to after this instruction.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Instruction::Branch { target } => vec![*target],
+            Instruction::ConditionalBranch {
+                then_block,
+                else_block,
+                ..
+            } => vec![*then_block, *else_block],
+            Instruction::Switch { default, cases, .. } => std::iter::once(*default)
+                .chain(cases.iter().map(|(_, block_id)| *block_id))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Whether this instruction ends a block. Every block must end with
+    /// exactly one of these.
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Return { .. }
+                | Instruction::Branch { .. }
+                | Instruction::ConditionalBranch { .. }
+                | Instruction::Switch { .. }
+                | Instruction::Unreachable
+        )
+    }
+
+    fn des_add(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::Add { result, a, b })
+    }
+
+    fn des_sub(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::Sub { result, a, b })
+    }
+
+    fn des_mul(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::Mul { result, a, b })
+    }
+
+    fn des_div(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::Div { result, a, b })
+    }
+
+    fn des_rem(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::Rem { result, a, b })
+    }
+
+    fn des_neg(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::Neg { result, source })
+    }
+
+    fn des_and(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::And { result, a, b })
+    }
+
+    fn des_or(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::Or { result, a, b })
+    }
+
+    fn des_xor(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::Xor { result, a, b })
+    }
+
+    fn des_not(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::Not { result, source })
+    }
+
+    fn des_shl(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::ShiftLeft { result, a, b })
+    }
+
+    fn des_shr(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::ShiftRight { result, a, b })
+    }
+
+    fn des_fadd(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::FAdd { result, a, b })
+    }
+
+    fn des_fsub(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::FSub { result, a, b })
+    }
+
+    fn des_fmul(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::FMul { result, a, b })
+    }
+
+    fn des_fdiv(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::FDiv { result, a, b })
+    }
+
+    fn des_frem(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::FRem { result, a, b })
+    }
+
+    fn des_fneg(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::FNeg { result, source })
+    }
+
+    fn des_fcmp(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let b = Instruction::get_register(args.pop().unwrap())?;
+        let a = Instruction::get_register(args.pop().unwrap())?;
+        let comparison = Instruction::get_float_comparison(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::FloatCompare {
+            result,
+            comparison,
+            a,
+            b,
+        })
+    }
+
+    fn des_itof(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, convert_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::IntToFloat {
+            result,
+            source,
+            convert_into,
+        })
+    }
+
+    fn des_ftoi(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, convert_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::FloatToInt {
+            result,
+            source,
+            convert_into,
+        })
+    }
+
+    fn des_compare(comparison: Comparison, args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, a, b) = Instruction::des_binary(args)?;
+        Ok(Instruction::Compare {
+            result,
+            comparison,
+            a,
+            b,
+        })
+    }
+
+    fn des_intrinsic(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let arguments = Instruction::get_arr(args.pop().unwrap())?
+            .into_iter()
+            .map(Instruction::get_register)
+            .collect::<Result<Vec<_>, _>>()?;
+        let name = Instruction::get_string(args.pop().unwrap())?;
+        let result = Instruction::get_optional_register(args.pop().unwrap())?;
+
+        if IntrinsicKind::parse(&name).is_none() {
+            return Err(format!("unknown intrinsic `{}`", name));
+        }
+
+        Ok(Instruction::Intrinsic {
+            result,
+            name,
+            arguments,
+        })
+    }
+
+    fn des_atomic_load(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let ordering = Instruction::get_ordering(args.pop().unwrap())?;
+        let pointer = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        if let MemoryOrdering::Release | MemoryOrdering::AcquireRelease = ordering {
+            return Err(format!("atomic loads cannot have {:?} ordering", ordering));
+        }
+
+        Ok(Instruction::AtomicLoad {
+            result,
+            pointer,
+            ordering,
+        })
+    }
+
+    fn des_atomic_store(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let ordering = Instruction::get_ordering(args.pop().unwrap())?;
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let pointer = Instruction::get_register(args.pop().unwrap())?;
+
+        if let MemoryOrdering::Acquire | MemoryOrdering::AcquireRelease = ordering {
+            return Err(format!("atomic stores cannot have {:?} ordering", ordering));
+        }
+
+        Ok(Instruction::AtomicStore {
+            pointer,
+            source,
+            ordering,
+        })
+    }
+
+    fn des_atomic_rmw(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let ordering = Instruction::get_ordering(args.pop().unwrap())?;
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let pointer = Instruction::get_register(args.pop().unwrap())?;
+        let operation = Instruction::get_atomic_operation(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        if let MemoryOrdering::Unordered = ordering {
+            return Err(format!("atomic_rmw cannot have {:?} ordering", ordering));
+        }
+
+        Ok(Instruction::AtomicRmw {
+            result,
+            operation,
+            pointer,
+            source,
+            ordering,
+        })
+    }
+
+    fn des_cmpxchg(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let failure_ordering = Instruction::get_ordering(args.pop().unwrap())?;
+        let success_ordering = Instruction::get_ordering(args.pop().unwrap())?;
+        let replacement = Instruction::get_register(args.pop().unwrap())?;
+        let expected = Instruction::get_register(args.pop().unwrap())?;
+        let pointer = Instruction::get_register(args.pop().unwrap())?;
+        let success = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        if let MemoryOrdering::Unordered = success_ordering {
+            return Err(format!(
+                "cmpxchg cannot have {:?} ordering",
+                success_ordering
+            ));
+        }
+
+        match failure_ordering {
+            MemoryOrdering::Unordered
+            | MemoryOrdering::Release
+            | MemoryOrdering::AcquireRelease => {
+                return Err(format!(
+                    "cmpxchg cannot have {:?} ordering when it fails",
+                    failure_ordering
+                ))
+            }
+            _ if failure_ordering.strength() > success_ordering.strength() => {
+                return Err(format!(
+                    "cmpxchg cannot have a stronger ordering when it fails ({:?}) than when it succeeds ({:?})",
+                    failure_ordering, success_ordering
+                ))
+            }
+            _ => {}
+        }
+
+        Ok(Instruction::CompareExchange {
+            result,
+            success,
+            pointer,
+            expected,
+            replacement,
+            success_ordering,
+            failure_ordering,
+        })
+    }
+
+    fn des_fence(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let ordering = Instruction::get_ordering(args.pop().unwrap())?;
+
+        if let MemoryOrdering::Unordered | MemoryOrdering::Monotonic = ordering {
+            return Err(format!("fences cannot have {:?} ordering", ordering));
+        }
+
+        Ok(Instruction::Fence { ordering })
+    }
+
+    fn des_add_checked(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, overflow, a, b) = Instruction::des_checked(args)?;
+        Ok(Instruction::AddChecked {
+            result,
+            overflow,
+            a,
+            b,
+        })
+    }
+
+    fn des_sub_checked(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, overflow, a, b) = Instruction::des_checked(args)?;
+        Ok(Instruction::SubChecked {
+            result,
+            overflow,
+            a,
+            b,
+        })
+    }
+
+    fn des_mul_checked(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, overflow, a, b) = Instruction::des_checked(args)?;
+        Ok(Instruction::MulChecked {
+            result,
+            overflow,
+            a,
+            b,
+        })
+    }
+
+    /// Deserializes the `[result, overflow, a, b]` arguments of checked
+    /// arithmetic.
+    fn des_checked(
+        mut args: Vec<JsValue>,
+    ) -> Result<(Register, Register, Register, Register), String> {
+        let b = Instruction::get_register(args.pop().unwrap())?;
+        let a = Instruction::get_register(args.pop().unwrap())?;
+        let overflow = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok((result, overflow, a, b))
+    }
+
+    /// Deserializes the `[result, a, b]` arguments shared by every
+    /// instruction that takes two operands.
+    fn des_binary(mut args: Vec<JsValue>) -> Result<(Register, Register, Register), String> {
+        let b = Instruction::get_register(args.pop().unwrap())?;
+        let a = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok((result, a, b))
+    }
+
+    fn des_ld_param(parameters: usize, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let parameter_number = Instruction::get_number(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        if parameter_number < 0 || parameter_number as usize >= parameters {
+            return Err(format!(
+                "parameter number out of bounds - mathematical range: [0, {}), value: {}",
+                parameters, parameter_number
+            ));
+        }
+
+        Ok(Instruction::LoadParameter {
+            result,
+            parameter_number,
+        })
+    }
+
+    fn des_ld_const(meta: &JsMeta, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let constant = Instruction::get_value(args.pop().unwrap())?;
+        let type_id = Instruction::get_type_id(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        if let TypeDefinition::Integer { .. } = meta.try_get_type(type_id)? {
+            constant.warn_if_imprecise();
+        }
+
+        Ok(Instruction::LoadConstant {
+            result,
+            type_id,
+            constant,
+        })
+    }
+
+    fn des_call(meta: &JsMeta, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let parameters = Instruction::get_arr(args.pop().unwrap())?
+            .into_iter()
+            .map(Instruction::get_register)
+            .collect::<Result<Vec<_>, _>>()?;
+        let method_id = Instruction::get_method_id(args.pop().unwrap())?;
+        let result = Instruction::get_optional_register(args.pop().unwrap())?;
+
+        let callee = meta.get_method(method_id)?;
+
+        let arity_matches = if callee.variadic {
+            parameters.len() >= callee.parameters.len()
+        } else {
+            parameters.len() == callee.parameters.len()
+        };
+
+        if !arity_matches {
+            return Err(format!(
+                "method `{}` takes {}{} parameter(s), but {} were given",
+                callee.name,
+                if callee.variadic { "at least " } else { "" },
+                callee.parameters.len(),
+                parameters.len()
+            ));
+        }
+
+        if let (Some(result), TypeDefinition::Void) = (result, meta.get_type(callee.return_type)) {
+            return Err(format!(
+                "method `{}` returns void, so it has no value to store in register {}",
+                callee.name, result
+            ));
+        }
+
+        // the types of the arguments are checked by the verifier, as they may
+        // be defined by instructions that haven't been emitted yet
+        Ok(Instruction::Call {
+            result,
+            method_id,
+            parameters,
+        })
+    }
+
+    fn des_fn_addr(meta: &JsMeta, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let method_id = Instruction::get_method_id(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        meta.get_method(method_id)?;
+
+        Ok(Instruction::MethodAddress { result, method_id })
+    }
+
+    fn des_call_indirect(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let parameters = Instruction::get_arr(args.pop().unwrap())?
+            .into_iter()
+            .map(Instruction::get_register)
+            .collect::<Result<Vec<_>, _>>()?;
+        let pointer = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_optional_register(args.pop().unwrap())?;
+
+        Ok(Instruction::CallIndirect {
+            result,
+            pointer,
+            parameters,
+        })
+    }
+
+    fn des_ret(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        Ok(Instruction::Return {
+            result: match args.pop() {
+                Some(arg) => Instruction::get_optional_register(arg)?,
+                None => None,
+            },
+        })
+    }
+
+    fn des_trunc(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, truncate_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::Truncate {
+            result,
+            truncate_into,
+            source,
+        })
+    }
+
+    fn des_sext(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, extend_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::SignExtend {
+            result,
+            source,
+            extend_into,
+        })
+    }
+
+    fn des_zext(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, extend_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::ZeroExtend {
+            result,
+            source,
+            extend_into,
+        })
+    }
+
+    fn des_cast(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, cast_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::Cast {
+            result,
+            source,
+            cast_into,
+        })
+    }
+
+    fn des_bitcast(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, cast_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::Bitcast {
+            result,
+            source,
+            cast_into,
+        })
+    }
+
+    /// Deserializes the `[result, type, source]` arguments shared by every
+    /// instruction that converts a value into another type.
+    fn des_conversion(mut args: Vec<JsValue>) -> Result<(Register, TypeId, Register), String> {
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let type_id = Instruction::get_type_id(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok((result, type_id, source))
+    }
+
+    fn des_br(blocks: usize, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let target = Instruction::get_block_id(blocks, args.pop().unwrap())?;
+
+        Ok(Instruction::Branch { target })
+    }
+
+    fn des_cond_br(blocks: usize, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let else_block = Instruction::get_block_id(blocks, args.pop().unwrap())?;
+        let then_block = Instruction::get_block_id(blocks, args.pop().unwrap())?;
+        let condition = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::ConditionalBranch {
+            condition,
+            then_block,
+            else_block,
+        })
+    }
+
+    fn des_switch(blocks: usize, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let cases = Instruction::get_arr(args.pop().unwrap())?
+            .into_iter()
+            .map(|pair| {
+                let mut pair = Instruction::get_arr(pair)?;
+
+                if pair.len() != 2 {
+                    return Err("expected switch case to be [constant, block]".to_owned());
+                }
+
+                let block_id = Instruction::get_block_id(blocks, pair.pop().unwrap())?;
+                let constant = Instruction::get_value(pair.pop().unwrap())?;
+                constant.warn_if_imprecise();
+                Ok((constant, block_id))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let default = Instruction::get_block_id(blocks, args.pop().unwrap())?;
+        let condition = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::Switch {
+            condition,
+            default,
+            cases,
+        })
+    }
+
+    fn des_select(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let b = Instruction::get_register(args.pop().unwrap())?;
+        let a = Instruction::get_register(args.pop().unwrap())?;
+        let condition = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::Select {
+            result,
+            condition,
+            a,
+            b,
+        })
+    }
+
+    fn des_phi(
+        meta: &JsMeta,
+        blocks: usize,
+        mut args: Vec<JsValue>,
+    ) -> Result<Instruction, String> {
+        let incoming = Instruction::get_arr(args.pop().unwrap())?
+            .into_iter()
+            .map(|pair| {
+                let mut pair = Instruction::get_arr(pair)?;
+
+                if pair.len() != 2 {
+                    return Err("expected phi incoming value to be [register, block]".to_owned());
+                }
+
+                let block_id = Instruction::get_block_id(blocks, pair.pop().unwrap())?;
+                let register = Instruction::get_register(pair.pop().unwrap())?;
+                Ok((register, block_id))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let type_id = Instruction::get_type_id(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        meta.try_get_type(type_id)?;
+
+        Ok(Instruction::Phi {
+            result,
+            type_id,
+            incoming,
+        })
+    }
+
+    fn des_load(locals: usize, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let local_id = Instruction::get_local_id(locals, args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::LoadLocal { result, local_id })
+    }
+
+    fn des_store(locals: usize, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let local_id = Instruction::get_local_id(locals, args.pop().unwrap())?;
+
+        Ok(Instruction::StoreLocal { local_id, source })
+    }
+
+    fn des_addr_of(locals: usize, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let local_id = Instruction::get_local_id(locals, args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::AddressOf { result, local_id })
+    }
+
+    fn des_load_ptr(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let pointer = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::LoadPointer { result, pointer })
+    }
+
+    fn des_store_ptr(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let pointer = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::StorePointer { pointer, source })
+    }
+
+    fn des_gep(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, pointer, index) = Instruction::des_binary(args)?;
+        Ok(Instruction::Offset {
+            result,
+            pointer,
+            index,
+        })
+    }
+
+    fn des_ptrtoint(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, convert_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::PointerToInt {
+            result,
+            source,
+            convert_into,
+        })
+    }
+
+    fn des_inttoptr(args: Vec<JsValue>) -> Result<Instruction, String> {
+        let (result, convert_into, source) = Instruction::des_conversion(args)?;
+        Ok(Instruction::IntToPointer {
+            result,
+            source,
+            convert_into,
+        })
+    }
+
+    fn des_ld_zero(meta: &JsMeta, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let type_id = Instruction::get_type_id(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        meta.try_get_type(type_id)?;
+
+        Ok(Instruction::LoadZero { result, type_id })
+    }
+
+    fn des_extract_field(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let index = Instruction::get_field_index(args.pop().unwrap())?;
+        let aggregate = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::ExtractField {
+            result,
+            aggregate,
+            index,
+        })
+    }
+
+    fn des_insert_field(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let source = Instruction::get_register(args.pop().unwrap())?;
+        let index = Instruction::get_field_index(args.pop().unwrap())?;
+        let aggregate = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::InsertField {
+            result,
+            aggregate,
+            index,
+            source,
+        })
+    }
+
+    fn des_field_ptr(mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let index = Instruction::get_field_index(args.pop().unwrap())?;
+        let pointer = Instruction::get_register(args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::FieldPointer {
+            result,
+            pointer,
+            index,
+        })
+    }
+
+    fn des_global_addr(globals: usize, mut args: Vec<JsValue>) -> Result<Instruction, String> {
+        let global_id = Instruction::get_global_id(globals, args.pop().unwrap())?;
+        let result = Instruction::get_register(args.pop().unwrap())?;
+
+        Ok(Instruction::GlobalAddress { result, global_id })
+    }
+
+    fn get_register(arg: JsValue) -> Result<Register, String> {
+        match arg {
+            JsValue::Int(i) => Ok(i),
+            _ => Err(format!("unable to get register from arg {:?}", arg)),
+        }
+    }
+
+    /// Gets a register, where `null` or `undefined` means there is none.
+    fn get_optional_register(arg: JsValue) -> Result<Option<Register>, String> {
+        match arg {
+            JsValue::Null | JsValue::Undefined => Ok(None),
+            _ => Instruction::get_register(arg).map(Some),
+        }
+    }
+
+    fn get_number(arg: JsValue) -> Result<i32, String> {
+        match arg {
+            JsValue::Int(i) => Ok(i),
+            _ => Err(format!("unable to get number from arg {:?}", arg)),
+        }
+    }
+
+    fn get_field_index(arg: JsValue) -> Result<u32, String> {
+        match arg {
+            JsValue::Int(i) if i >= 0 => Ok(i as u32),
+            _ => Err(format!("unable to get field index from arg {:?}", arg)),
+        }
+    }
+
+    fn get_type_id(arg: JsValue) -> Result<TypeId, String> {
+        match arg {
+            JsValue::Int(i) => Ok(i),
+            _ => Err(format!("unable to get type from arg {:?}", arg)),
+        }
+    }
+
+    /// Numbers are kept as they are, as whether they're an integer or a float
+    /// depends on the type they're used as.
+    fn get_value(arg: JsValue) -> Result<Constant, String> {
+        let constant = match &arg {
+            JsValue::Int(i) => Some(Constant::from_i64(*i as i64)),
+            JsValue::Float(f) => Some(Constant::Float(*f)),
+            // BigInts are converted into decimal strings by the primer
+            JsValue::String(text) => Constant::parse_integer(text.as_str())
+                .or_else(|| text.parse::<f64>().ok().map(Constant::Float)),
+            _ => None,
+        };
+
+        constant.ok_or_else(|| format!("unable to get constant from arg {:?}", arg))
+    }
+
+    fn get_float_comparison(arg: JsValue) -> Result<FloatComparison, String> {
+        let comparison = match &arg {
+            JsValue::String(predicate) => FloatComparison::parse(predicate.as_str()),
+            _ => None,
+        };
+
+        comparison.ok_or_else(|| format!("unable to get float comparison from arg {:?}", arg))
+    }
+
+    fn get_ordering(arg: JsValue) -> Result<MemoryOrdering, String> {
+        let ordering = match &arg {
+            JsValue::String(name) => MemoryOrdering::parse(name.as_str()),
+            _ => None,
+        };
+
+        ordering.ok_or_else(|| format!("unable to get memory ordering from arg {:?}", arg))
+    }
+
+    fn get_atomic_operation(arg: JsValue) -> Result<AtomicOperation, String> {
+        let operation = match &arg {
+            JsValue::String(name) => AtomicOperation::parse(name.as_str()),
+            _ => None,
+        };
+
+        operation.ok_or_else(|| format!("unable to get atomic operation from arg {:?}", arg))
+    }
+
+    fn get_string(arg: JsValue) -> Result<String, String> {
+        match arg {
+            JsValue::String(text) => Ok(text),
+            _ => Err(format!("unable to get string from arg {:?}", arg)),
+        }
+    }
+
+    fn get_arr(arg: JsValue) -> Result<Vec<JsValue>, String> {
+        match arg {
+            JsValue::Array(values) => Ok(values),
+            _ => Err(format!("unable to get array from arg {:?}", arg)),
+        }
+    }
+
+    fn get_block_id(blocks: usize, arg: JsValue) -> Result<BlockId, String> {
+        let block_id = match arg {
+            JsValue::Int(i) => i,
+            _ => return Err(format!("unable to get block from arg {:?}", arg)),
+        };
+
+        if block_id < 0 || block_id as usize >= blocks {
+            return Err(format!(
+                "block id out of bounds - mathematical range: [0, {}), value: {}",
+                blocks, block_id
+            ));
+        }
+
+        Ok(block_id)
+    }
+
+    fn get_global_id(globals: usize, arg: JsValue) -> Result<GlobalId, String> {
+        let global_id = match arg {
+            JsValue::Int(i) => i,
+            _ => return Err(format!("unable to get global from arg {:?}", arg)),
+        };
+
+        if global_id < 0 || global_id as usize >= globals {
+            return Err(format!(
+                "global id out of bounds - mathematical range: [0, {}), value: {}",
+                globals, global_id
+            ));
+        }
+
+        Ok(global_id)
+    }
+
+    fn get_local_id(locals: usize, arg: JsValue) -> Result<LocalId, String> {
+        let local_id = match arg {
+            JsValue::Int(i) => i,
+            _ => return Err(format!("unable to get local from arg {:?}", arg)),
+        };
+
+        if local_id < 0 || local_id as usize >= locals {
+            return Err(format!(
+                "local id out of bounds - mathematical range: [0, {}), value: {}",
+                locals, local_id
+            ));
+        }
+
+        Ok(local_id)
+    }
+
+    fn get_method_id(arg: JsValue) -> Result<MethodId, String> {
+        match arg {
+            JsValue::Int(i) => Ok(i),
+            _ => Err(format!("unable to get method from arg {:?}", arg)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessOrEqual,
+    GreaterThan,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FloatComparison {
+    /// Neither operand is NaN, and the comparison holds.
+    Ordered(Comparison),
+    /// Either operand is NaN, or the comparison holds.
+    Unordered(Comparison),
+    /// Neither operand is NaN.
+    NotNaN,
+    /// Either operand is NaN.
+    IsNaN,
+}
+
+impl FloatComparison {
+    /// Parses one of LLVM's `fcmp` predicates, such as `olt` or `une`.
+    pub fn parse(predicate: &str) -> Option<Self> {
+        let comparison = |name: &str| match name {
+            "eq" => Some(Comparison::Equal),
+            "ne" => Some(Comparison::NotEqual),
+            "lt" => Some(Comparison::LessThan),
+            "le" => Some(Comparison::LessOrEqual),
+            "gt" => Some(Comparison::GreaterThan),
+            "ge" => Some(Comparison::GreaterOrEqual),
+            _ => None,
+        };
+
+        match predicate {
+            "ord" => Some(FloatComparison::NotNaN),
+            "uno" => Some(FloatComparison::IsNaN),
+            _ if predicate.starts_with('o') => {
+                comparison(&predicate[1..]).map(FloatComparison::Ordered)
+            }
+            _ if predicate.starts_with('u') => {
+                comparison(&predicate[1..]).map(FloatComparison::Unordered)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How an atomic operation is ordered with respect to other memory accesses,
+/// from weakest to strongest.
+#[derive(Debug, Clone, Copy)]
+pub enum MemoryOrdering {
+    Unordered,
+    Monotonic,
+    Acquire,
+    Release,
+    AcquireRelease,
+    SequentiallyConsistent,
+}
+
+impl MemoryOrdering {
+    /// Parses one of `unordered`, `monotonic`, `acquire`, `release`,
+    /// `acq_rel` or `seq_cst`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "unordered" => Some(MemoryOrdering::Unordered),
+            "monotonic" => Some(MemoryOrdering::Monotonic),
+            "acquire" => Some(MemoryOrdering::Acquire),
+            "release" => Some(MemoryOrdering::Release),
+            "acq_rel" => Some(MemoryOrdering::AcquireRelease),
+            "seq_cst" => Some(MemoryOrdering::SequentiallyConsistent),
+            _ => None,
+        }
+    }
+
+    /// How strong the ordering is. `acquire` and `release` are equally
+    /// strong, as neither implies the other.
+    pub fn strength(self) -> u32 {
+        match self {
+            MemoryOrdering::Unordered => 0,
+            MemoryOrdering::Monotonic => 1,
+            MemoryOrdering::Acquire | MemoryOrdering::Release => 2,
+            MemoryOrdering::AcquireRelease => 3,
+            MemoryOrdering::SequentiallyConsistent => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum AtomicOperation {
+    Exchange,
+    Add,
+    Sub,
+    And,
+    Nand,
+    Or,
+    Xor,
+    /// Signed or unsigned, depending on the type of the value.
+    Max,
+    /// Signed or unsigned, depending on the type of the value.
+    Min,
+}
+
+impl AtomicOperation {
+    /// Parses one of LLVM's `atomicrmw` operations, such as `xchg` or `add`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "xchg" => Some(AtomicOperation::Exchange),
+            "add" => Some(AtomicOperation::Add),
+            "sub" => Some(AtomicOperation::Sub),
+            "and" => Some(AtomicOperation::And),
+            "nand" => Some(AtomicOperation::Nand),
+            "or" => Some(AtomicOperation::Or),
+            "xor" => Some(AtomicOperation::Xor),
+            "max" => Some(AtomicOperation::Max),
+            "min" => Some(AtomicOperation::Min),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    /// An integer of any size, stored as a sign and a magnitude split into
+    /// little endian 64 bit words.
+    Integer {
+        negative: bool,
+        magnitude: Vec<u64>,
+    },
+    Float(f64),
+}
+
+impl Constant {
+    pub fn from_i64(value: i64) -> Self {
+        Constant::Integer {
+            negative: value < 0,
+            magnitude: vec![(value as i128).abs() as u64],
         }
     }
 
-    fn des_add(mut args: Vec<JsValue>) -> Instruction {
-        let b = Instruction::get_register(args.pop().unwrap());
-        let a = Instruction::get_register(args.pop().unwrap());
-        let result = Instruction::get_register(args.pop().unwrap());
+    /// Parses a decimal or `0x` prefixed hexadecimal integer, optionally
+    /// preceded by a `-`.
+    pub fn parse_integer(text: &str) -> Option<Self> {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
 
-        Instruction::Add { result, a, b }
-    }
-
-    fn des_ld_param(parameters: usize, mut args: Vec<JsValue>) -> Instruction {
-        let parameter_number = Instruction::get_number(args.pop().unwrap());
-        let result = Instruction::get_register(args.pop().unwrap());
+        let (radix, digits) = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(rest) => (16, rest),
+            None => (10, text),
+        };
 
-        if parameter_number < 0 || parameter_number as usize > parameters {
-            panic!(
-                "parameter number out of bounds - mathematical range: [0, {}], value: {}",
-                parameters, parameter_number
-            );
+        if digits.is_empty() {
+            return None;
         }
 
-        Instruction::LoadParameter {
-            result,
-            parameter_number,
+        let mut magnitude = vec![0u64];
+        for digit in digits.chars() {
+            let mut carry = digit.to_digit(radix)? as u128;
+
+            for word in magnitude.iter_mut() {
+                let value = (*word as u128) * radix as u128 + carry;
+                *word = value as u64;
+                carry = value >> 64;
+            }
+
+            if carry != 0 {
+                magnitude.push(carry as u64);
+            }
         }
+
+        Some(Constant::Integer {
+            negative,
+            magnitude,
+        })
     }
 
-    fn des_ld_const<F: Fn(TypeId) -> bool>(is_valid: F, mut args: Vec<JsValue>) -> Instruction {
-        let constant = Instruction::get_value(args.pop().unwrap());
-        let type_id = Instruction::get_type_id(args.pop().unwrap());
-        let result = Instruction::get_register(args.pop().unwrap());
+    /// Whether this is a whole JS number beyond the range where every integer
+    /// can be held exactly, so it may have been rounded from the integer the
+    /// lowerer wrote.
+    pub fn is_imprecise(&self) -> bool {
+        // the largest integer a JS number can hold without losing precision
+        const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
 
-        if !is_valid(type_id) {
-            panic!("invalid type {}", type_id);
+        match self {
+            Constant::Float(value) => value.fract() == 0.0 && value.abs() > MAX_SAFE_INTEGER,
+            Constant::Integer { .. } => false,
         }
+    }
 
-        Instruction::LoadConstant {
-            result,
-            type_id,
-            constant,
+    /// Warns if an integer constant may not be what the lowerer wrote. It's
+    /// still used as is, as it may well be exact.
+    fn warn_if_imprecise(&self) {
+        if self.is_imprecise() {
+            eprintln!(
+                "warning: integer constant {} is too large to be exact as a JS number, pass it as a BigInt to make sure it isn't rounded",
+                self
+            );
         }
     }
 
-    fn des_call(mut args: Vec<JsValue>) -> Instruction {
-        let parameters = Instruction::get_arr(args.pop().unwrap())
-            .into_iter()
-            .map(Instruction::get_register)
-            .collect::<Vec<_>>();
-        let method_id = Instruction::get_method_id(args.pop().unwrap());
-        let result = Instruction::get_register(args.pop().unwrap());
+    /// The exact integer a float holds, if it holds one.
+    fn from_integral_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() || value.fract() != 0.0 {
+            return None;
+        }
 
-        eprintln!("// TODO: sanitize deserialize call");
+        let bits = value.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i32;
+        let negative = value.is_sign_negative();
 
-        Instruction::Call {
-            result,
-            method_id,
-            parameters,
+        // subnormals are never integral, so this can only be a zero
+        if exponent == 0 {
+            return Some(Constant::Integer {
+                negative,
+                magnitude: vec![0],
+            });
         }
-    }
 
-    fn des_ret(mut args: Vec<JsValue>) -> Instruction {
-        Instruction::Return {
-            result: args.pop().map(Instruction::get_register),
-        }
+        // the value is `mantissa * 2^shift`, and as it's integral, shifting
+        // right loses no bits
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let shift = exponent - 1075;
+
+        let magnitude = if shift <= 0 {
+            vec![mantissa >> -shift]
+        } else {
+            let mut words = vec![0; shift as usize / 64];
+            let shifted = (mantissa as u128) << (shift % 64);
+            words.push(shifted as u64);
+            words.push((shifted >> 64) as u64);
+            words
+        };
+
+        Some(Constant::Integer {
+            negative,
+            magnitude,
+        })
     }
 
-    fn des_trunc(mut args: Vec<JsValue>) -> Instruction {
-        let source = Instruction::get_register(args.pop().unwrap());
-        let truncate_into = Instruction::get_type_id(args.pop().unwrap());
-        let result = Instruction::get_register(args.pop().unwrap());
+    /// Produces the two's complement representation of an integer constant as
+    /// little endian 64 bit words, ensuring it fits within the given type.
+    /// Floats are accepted if they hold an integer exactly.
+    pub fn to_words(&self, signed: bool, bits: u32) -> Result<Vec<u64>, String> {
+        let (negative, magnitude) = match self {
+            Constant::Integer {
+                negative,
+                magnitude,
+            } => (negative, magnitude),
+            Constant::Float(value) => {
+                return match Constant::from_integral_f64(*value) {
+                    Some(constant) => constant.to_words(signed, bits),
+                    None => Err(format!("expected an integer constant, got {}", self)),
+                }
+            }
+        };
 
-        Instruction::Truncate {
-            result,
-            truncate_into,
-            source,
+        let is_zero = magnitude.iter().all(|word| *word == 0);
+        let negative = *negative && !is_zero;
+
+        // the number of bits needed to represent the magnitude
+        let magnitude_bits = magnitude
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, word)| **word != 0)
+            .map(|(index, word)| index as u32 * 64 + (64 - word.leading_zeros()))
+            .unwrap_or(0);
+
+        let fits = match (signed, negative) {
+            (false, true) => false,
+            (false, false) => magnitude_bits <= bits,
+            (true, false) => magnitude_bits < bits,
+            // the magnitude of the smallest signed value is a lone 1 bit
+            (true, true) => {
+                let is_power_of_two = magnitude.iter().map(|w| w.count_ones()).sum::<u32>() == 1;
+                magnitude_bits < bits || (magnitude_bits == bits && is_power_of_two)
+            }
+        };
+
+        if !fits {
+            return Err(format!(
+                "constant {} does not fit in {} {} bit integer",
+                self,
+                if signed { "a signed" } else { "an unsigned" },
+                bits
+            ));
         }
-    }
 
-    fn get_register(arg: JsValue) -> Register {
-        match arg {
-            JsValue::Int(i) => i,
-            _ => panic!("unable to get register from arg {:?}", arg),
+        let word_count = ((bits as usize + 63) / 64).max(1);
+        let mut words = magnitude.clone();
+        words.resize(word_count, 0);
+
+        if negative {
+            let mut carry = true;
+            for word in words.iter_mut() {
+                let (value, overflowed) = (!*word).overflowing_add(carry as u64);
+                *word = value;
+                carry = overflowed;
+            }
         }
-    }
 
-    fn get_number(arg: JsValue) -> i32 {
-        match arg {
-            JsValue::Int(i) => i,
-            _ => panic!("unable to get number from arg {:?}", arg),
+        // mask off the bits above the width of the type
+        if bits % 64 != 0 {
+            let last = words.last_mut().unwrap();
+            *last &= (1u64 << (bits % 64)) - 1;
         }
+
+        Ok(words)
     }
 
-    fn get_type_id(arg: JsValue) -> TypeId {
-        match arg {
-            JsValue::Int(i) => i,
-            _ => panic!("unable to get register from arg {:?}", arg),
+    /// Produces the value of a constant as a float. Integers too large to be
+    /// represented exactly are rounded.
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Constant::Integer {
+                negative,
+                magnitude,
+            } => {
+                let value = magnitude.iter().rev().fold(0.0, |value, word| {
+                    value * 18446744073709551616.0 + *word as f64
+                });
+
+                if *negative {
+                    -value
+                } else {
+                    value
+                }
+            }
+            Constant::Float(value) => *value,
         }
     }
+}
 
-    fn get_value(arg: JsValue) -> Constant {
-        match arg {
-            JsValue::Int(i) => Constant::Number(i),
-            _ => panic!("unable to get constant from arg {:?}", arg),
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (negative, magnitude) = match self {
+            Constant::Integer {
+                negative,
+                magnitude,
+            } => (negative, magnitude),
+            Constant::Float(value) => return write!(f, "{}", value),
+        };
+
+        if *negative {
+            write!(f, "-")?;
         }
-    }
 
-    fn get_arr(arg: JsValue) -> Vec<JsValue> {
-        match arg {
-            JsValue::Array(values) => values,
-            _ => panic!("unable to get array from arg {:?}", arg),
+        write!(f, "0x")?;
+
+        let mut words = magnitude.iter().rev().skip_while(|word| **word == 0);
+        match words.next() {
+            Some(first) => write!(f, "{:x}", first)?,
+            None => return write!(f, "0"),
         }
-    }
 
-    fn get_method_id(arg: JsValue) -> MethodId {
-        match arg {
-            JsValue::Int(i) => i,
-            _ => panic!("unable to get method from arg {:?}", arg),
+        for word in words {
+            write!(f, "{:016x}", word)?;
         }
-    }
-}
 
-#[derive(Debug, Clone)]
-pub enum Constant {
-    Number(i32),
+        Ok(())
+    }
 }
 
+/// Injects the `__compiler_*` callbacks and the primer into the context.
+///
+/// The callbacks return errors rather than panicking when they're given bad
+/// IR, which QuickJS throws back into the primer. The primer then rethrows
+/// them as `Error`s, so that their stack trace points to the lowerer.
 pub fn hook(context: &mut quick_js::Context) -> JsMetaHandle {
     let primer = include_str!("./primer.js");
-    let source_meta = Arc::new(Mutex::new(JsMeta {
-        main_id: None,
-        types: vec![],
-        methods: vec![],
-    }));
+    let source_meta = Arc::new(Mutex::new(JsMeta::new()));
 
     let meta = source_meta.clone();
     context
         .add_callback("__compiler_type", move |signed: bool, bits: i32| {
             let mut meta = meta.lock().unwrap();
 
-            let type_id = meta.add_type(signed, bits);
+            // the widest integer LLVM supports is 2^24 - 1 bits
+            if bits < 1 || bits > 16_777_215 {
+                return Err(format!(
+                    "expected integer to be between 1 and 16777215 bits, got {}",
+                    bits
+                ));
+            }
 
-            JsValue::Int(type_id)
+            let type_id = meta.add_type(TypeDefinition::Integer {
+                signed,
+                bits: bits as u32,
+            });
+
+            Ok(JsValue::Int(type_id))
         })
         .expect("expected to inject __compiler_type");
 
+    let meta = source_meta.clone();
+    context
+        .add_callback("__compiler_float_type", move |bits: i32| {
+            let mut meta = meta.lock().unwrap();
+
+            if ![16, 32, 64, 128].contains(&bits) {
+                return Err(format!(
+                    "expected float to be 16, 32, 64 or 128 bits, got {}",
+                    bits
+                ));
+            }
+
+            let type_id = meta.add_type(TypeDefinition::Float { bits: bits as u32 });
+
+            Ok(JsValue::Int(type_id))
+        })
+        .expect("expected to inject __compiler_float_type");
+
+    let meta = source_meta.clone();
+    context
+        .add_callback("__compiler_void_type", move || {
+            let mut meta = meta.lock().unwrap();
+
+            let type_id = meta.add_type(TypeDefinition::Void);
+
+            JsValue::Int(type_id)
+        })
+        .expect("expected to inject __compiler_void_type");
+
+    let meta = source_meta.clone();
+    context
+        .add_callback("__compiler_pointer_type", move |pointee: TypeId| {
+            let mut meta = meta.lock().unwrap();
+
+            // ensure the type exists
+            meta.try_get_type(pointee)?;
+
+            let type_id = meta.add_type(TypeDefinition::Pointer { pointee });
+
+            Ok::<_, String>(JsValue::Int(type_id))
+        })
+        .expect("expected to inject __compiler_pointer_type");
+
+    let meta = source_meta.clone();
+    context
+        .add_callback(
+            "__compiler_function_pointer_type",
+            move |return_type: TypeId, parameters: Vec<TypeId>, variadic: bool| {
+                let mut meta = meta.lock().unwrap();
+
+                // ensure the types exist, and that parameters can hold a value
+                meta.try_get_type(return_type)?;
+                for parameter in parameters.iter() {
+                    if let TypeDefinition::Void = meta.try_get_type(*parameter)? {
+                        return Err("parameters of function pointers cannot be void".to_owned());
+                    }
+                }
+
+                let type_id = meta.add_type(TypeDefinition::FunctionPointer {
+                    return_type,
+                    parameters,
+                    variadic,
+                });
+
+                Ok(JsValue::Int(type_id))
+            },
+        )
+        .expect("expected to inject __compiler_function_pointer_type");
+
+    let meta = source_meta.clone();
+    context
+        .add_callback(
+            "__compiler_struct_type",
+            move |name: String, fields: Vec<TypeId>| {
+                let mut meta = meta.lock().unwrap();
+
+                let type_id = meta.add_struct(name, fields)?;
+
+                Ok(JsValue::Int(type_id))
+            },
+        )
+        .expect("expected to inject __compiler_struct_type");
+
+    let meta = source_meta.clone();
+    context
+        .add_callback(
+            "__compiler_array_type",
+            move |element: TypeId, length: i32| {
+                let mut meta = meta.lock().unwrap();
+
+                // ensure the element type exists, and can hold a value
+                if let TypeDefinition::Void = meta.try_get_type(element)? {
+                    return Err("arrays cannot hold void".to_owned());
+                }
+
+                if length < 0 {
+                    return Err(format!("arrays cannot have a negative length ({})", length));
+                }
+
+                meta.add_type(TypeDefinition::Pointer { pointee: element });
+
+                let type_id = meta.add_type(TypeDefinition::Array {
+                    element,
+                    length: length as u32,
+                });
+
+                Ok(JsValue::Int(type_id))
+            },
+        )
+        .expect("expected to inject __compiler_array_type");
+
     let meta = source_meta.clone();
     context
         .add_callback("__compiler_set_main", move |method_id: MethodId| {
             let mut meta = meta.lock().unwrap();
 
-            // ensure the method exists
-            meta.get_method_mut(method_id);
+            // ensure the method exists, and has a body
+            let method = meta.get_method_mut(method_id)?;
+            if method.external {
+                return Err(format!("external method `{}` cannot be main", method.name));
+            }
 
             meta.main_id = Some(method_id);
 
-            JsValue::Undefined
+            Ok(JsValue::Undefined)
         })
         .expect("expected to inject __compiler_set_main");
 
@@ -315,13 +2846,43 @@ pub fn hook(context: &mut quick_js::Context) -> JsMetaHandle {
             move |name: String, return_type: TypeId, parameters: Vec<TypeId>| {
                 let mut meta = meta.lock().unwrap();
 
-                let method_id = meta.add_method(name, return_type, parameters);
+                let method_id = meta.add_method(name, return_type, parameters)?;
 
-                JsValue::Int(method_id)
+                Ok::<_, String>(JsValue::Int(method_id))
             },
         )
         .expect("expected to inject __compiler_generate_method");
 
+    let meta = source_meta.clone();
+    context
+        .add_callback(
+            "__compiler_set_method_options",
+            move |method_id: MethodId, options: JsValue| {
+                let mut meta = meta.lock().unwrap();
+
+                let options =
+                    MethodOptions::parse(meta.get_method(method_id)?.name.as_str(), options)?;
+                meta.set_method_options(method_id, options)?;
+
+                Ok::<_, String>(JsValue::Undefined)
+            },
+        )
+        .expect("expected to inject __compiler_set_method_options");
+
+    let meta = source_meta.clone();
+    context
+        .add_callback(
+            "__compiler_generate_extern",
+            move |name: String, return_type: TypeId, parameters: Vec<TypeId>, variadic: bool| {
+                let mut meta = meta.lock().unwrap();
+
+                let method_id = meta.add_extern(name, return_type, parameters, variadic)?;
+
+                Ok::<_, String>(JsValue::Int(method_id))
+            },
+        )
+        .expect("expected to inject __compiler_generate_extern");
+
     let meta = source_meta.clone();
     context
         .add_callback(
@@ -329,14 +2890,68 @@ pub fn hook(context: &mut quick_js::Context) -> JsMetaHandle {
             move |method_id: MethodId, name: String| {
                 let mut meta = meta.lock().unwrap();
 
-                let method = meta.get_method_mut(method_id);
-                let block_id = method.add_block(name);
+                let method = meta.get_method_mut(method_id)?;
+                let block_id = method.add_block(name)?;
 
-                JsValue::Int(block_id)
+                Ok::<_, String>(JsValue::Int(block_id))
             },
         )
         .expect("expected to inject __compiler_generate_method");
 
+    let meta = source_meta.clone();
+    context
+        .add_callback(
+            "__compiler_generate_local",
+            move |method_id: MethodId, type_id: TypeId| {
+                let mut meta = meta.lock().unwrap();
+
+                // ensure the type exists, and can hold a value
+                if let TypeDefinition::Void = meta.try_get_type(type_id)? {
+                    return Err("locals cannot be void".to_owned());
+                }
+
+                // `addr_of` produces a pointer to the local, so make sure its
+                // type exists for when the local's address is taken
+                meta.add_type(TypeDefinition::Pointer { pointee: type_id });
+
+                let method = meta.get_method_mut(method_id)?;
+                let local_id = method.add_local(type_id);
+
+                Ok(JsValue::Int(local_id))
+            },
+        )
+        .expect("expected to inject __compiler_generate_local");
+
+    let meta = source_meta.clone();
+    context
+        .add_callback(
+            "__compiler_generate_global",
+            move |name: String, type_id: TypeId, initializer: JsValue, mutable: bool| {
+                let mut meta = meta.lock().unwrap();
+
+                let initializer = match initializer {
+                    JsValue::Null | JsValue::Undefined => None,
+                    _ => Some(Instruction::get_value(initializer)?),
+                };
+
+                let global_id = meta.add_global(name, type_id, initializer, mutable)?;
+
+                Ok::<_, String>(JsValue::Int(global_id))
+            },
+        )
+        .expect("expected to inject __compiler_generate_global");
+
+    let meta = source_meta.clone();
+    context
+        .add_callback("__compiler_generate_string", move |value: String| {
+            let mut meta = meta.lock().unwrap();
+
+            let global_id = meta.add_string(value);
+
+            JsValue::Int(global_id)
+        })
+        .expect("expected to inject __compiler_generate_string");
+
     let meta = source_meta.clone();
     context
         .add_callback(
@@ -347,21 +2962,14 @@ pub fn hook(context: &mut quick_js::Context) -> JsMetaHandle {
                   values: Vec<JsValue>| {
                 let mut meta = meta.lock().unwrap();
 
-                let method = meta.get_method_mut(method_id);
-                let instruction = Instruction::deserialize(
-                    method.parameters.len(),
-                    |_| {
-                        eprintln!("TODO: verify method id");
-                        true
-                    },
-                    instruction,
-                    values,
-                );
-
-                let block = method.get_block_mut(block_id);
+                let method = meta.get_method(method_id)?;
+                let instruction = Instruction::deserialize(&meta, method, instruction, values)?;
+
+                let method = meta.get_method_mut(method_id)?;
+                let block = method.get_block_mut(block_id)?;
                 block.instructions.push(instruction);
 
-                JsValue::Undefined
+                Ok::<_, String>(JsValue::Undefined)
             },
         )
         .expect("expected to inject __compiler_generate_method");
@@ -372,3 +2980,411 @@ pub fn hook(context: &mut quick_js::Context) -> JsMetaHandle {
 
     source_meta
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str, signed: bool, bits: u32) -> Result<Vec<u64>, String> {
+        Constant::parse_integer(text)
+            .unwrap()
+            .to_words(signed, bits)
+    }
+
+    #[test]
+    fn parse_integer() {
+        assert!(Constant::parse_integer("").is_none());
+        assert!(Constant::parse_integer("-").is_none());
+        assert!(Constant::parse_integer("0x").is_none());
+        assert!(Constant::parse_integer("12a").is_none());
+        assert!(Constant::parse_integer("0xfg").is_none());
+
+        assert_eq!(words("42", false, 64), Ok(vec![42]));
+        assert_eq!(words("0x2A", false, 64), Ok(vec![42]));
+        assert_eq!(words("0X2a", false, 64), Ok(vec![42]));
+        assert_eq!(words("-0", true, 8), Ok(vec![0]));
+
+        // magnitudes carry into a second word
+        assert_eq!(words("18446744073709551616", false, 128), Ok(vec![0, 1]));
+        assert_eq!(
+            words("0xffffffffffffffffffffffffffffffff", false, 128),
+            Ok(vec![u64::MAX, u64::MAX])
+        );
+    }
+
+    #[test]
+    fn to_words_bool() {
+        assert_eq!(words("0", false, 1), Ok(vec![0]));
+        assert_eq!(words("1", false, 1), Ok(vec![1]));
+        assert!(words("2", false, 1).is_err());
+        assert!(words("-1", false, 1).is_err());
+
+        assert_eq!(words("-1", true, 1), Ok(vec![1]));
+        assert!(words("1", true, 1).is_err());
+    }
+
+    #[test]
+    fn to_words_i8() {
+        assert_eq!(words("127", true, 8), Ok(vec![0x7f]));
+        assert_eq!(words("-128", true, 8), Ok(vec![0x80]));
+        assert_eq!(words("-1", true, 8), Ok(vec![0xff]));
+        assert!(words("128", true, 8).is_err());
+        assert!(words("-129", true, 8).is_err());
+
+        assert_eq!(words("255", false, 8), Ok(vec![0xff]));
+        assert!(words("256", false, 8).is_err());
+        assert!(words("-1", false, 8).is_err());
+    }
+
+    #[test]
+    fn to_words_i64() {
+        assert_eq!(
+            words("9223372036854775807", true, 64),
+            Ok(vec![i64::MAX as u64])
+        );
+        assert_eq!(
+            words("-9223372036854775808", true, 64),
+            Ok(vec![i64::MIN as u64])
+        );
+        assert!(words("9223372036854775808", true, 64).is_err());
+        assert!(words("-9223372036854775809", true, 64).is_err());
+
+        assert_eq!(words("18446744073709551615", false, 64), Ok(vec![u64::MAX]));
+        assert!(words("18446744073709551616", false, 64).is_err());
+
+        assert_eq!(
+            Constant::from_i64(i64::MIN).to_words(true, 64),
+            Ok(vec![i64::MIN as u64])
+        );
+        assert_eq!(
+            Constant::from_i64(-1).to_words(true, 32),
+            Ok(vec![0xffff_ffff])
+        );
+    }
+
+    #[test]
+    fn to_words_i128() {
+        assert_eq!(words("-1", true, 128), Ok(vec![u64::MAX, u64::MAX]));
+        assert_eq!(
+            words("-170141183460469231731687303715884105728", true, 128),
+            Ok(vec![0, 1 << 63])
+        );
+        assert_eq!(
+            words("170141183460469231731687303715884105727", true, 128),
+            Ok(vec![u64::MAX, u64::MAX >> 1])
+        );
+        assert!(words("170141183460469231731687303715884105728", true, 128).is_err());
+        assert!(words("-170141183460469231731687303715884105729", true, 128).is_err());
+
+        // widths that aren't a multiple of 64 are masked in their last word
+        assert_eq!(words("-2", true, 65), Ok(vec![u64::MAX - 1, 1]));
+        assert!(words("0x10000000000000000", true, 65).is_err());
+    }
+
+    #[test]
+    fn to_words_float() {
+        assert_eq!(Constant::Float(1.0).to_words(true, 32), Ok(vec![1]));
+        assert_eq!(Constant::Float(-1.0).to_words(true, 8), Ok(vec![0xff]));
+        assert_eq!(Constant::Float(-0.0).to_words(false, 8), Ok(vec![0]));
+        assert!(Constant::Float(-1.0).to_words(false, 8).is_err());
+        assert!(Constant::Float(256.0).to_words(false, 8).is_err());
+
+        assert!(Constant::Float(0.5).to_words(true, 32).is_err());
+        assert!(Constant::Float(f64::INFINITY).to_words(true, 32).is_err());
+        assert!(Constant::Float(f64::NAN).to_words(true, 32).is_err());
+    }
+
+    #[test]
+    fn to_words_large_float() {
+        let float = |value: f64| Constant::Float(value);
+
+        // whole numbers beyond 2^53 are still converted exactly
+        assert_eq!(float(2f64.powi(53)).to_words(false, 64), Ok(vec![1 << 53]));
+        assert_eq!(
+            float(2f64.powi(53) + 2.0).to_words(false, 64),
+            Ok(vec![(1 << 53) + 2])
+        );
+        assert_eq!(float(2f64.powi(63)).to_words(false, 64), Ok(vec![1 << 63]));
+        assert!(float(2f64.powi(63)).to_words(true, 64).is_err());
+        assert_eq!(
+            float(-(2f64.powi(63))).to_words(true, 64),
+            Ok(vec![i64::MIN as u64])
+        );
+        assert_eq!(
+            float(2f64.powi(64) - 2f64.powi(11)).to_words(false, 64),
+            Ok(vec![u64::MAX - 0x7ff])
+        );
+        assert!(float(2f64.powi(64)).to_words(false, 64).is_err());
+        assert_eq!(float(2f64.powi(64)).to_words(false, 128), Ok(vec![0, 1]));
+        assert_eq!(
+            float(2f64.powi(64) - 2f64.powi(11)).to_words(true, 128),
+            Ok(vec![u64::MAX - 0x7ff, 0])
+        );
+        assert_eq!(
+            float(-(2f64.powi(127))).to_words(true, 128),
+            Ok(vec![0, 1 << 63])
+        );
+    }
+
+    #[test]
+    fn is_imprecise() {
+        assert!(!Constant::Float(9007199254740991.0).is_imprecise());
+        assert!(!Constant::Float(-9007199254740991.0).is_imprecise());
+        assert!(Constant::Float(9007199254740992.0).is_imprecise());
+        assert!(Constant::Float(-(2f64.powi(63))).is_imprecise());
+
+        assert!(!Constant::Float(0.5).is_imprecise());
+        assert!(!Constant::Float(f64::INFINITY).is_imprecise());
+        assert!(!Constant::parse_integer("18446744073709551615")
+            .unwrap()
+            .is_imprecise());
+    }
+
+    #[test]
+    fn get_value_keeps_floats() {
+        let constant = Instruction::get_value(JsValue::Float(-0.0)).unwrap();
+        assert!(constant.to_f64().is_sign_negative());
+        assert_eq!(constant.to_f64(), 0.0);
+
+        let constant = Instruction::get_value(JsValue::Float(3.0)).unwrap();
+        assert_eq!(constant.to_words(true, 32), Ok(vec![3]));
+        assert_eq!(constant.to_f64(), 3.0);
+    }
+
+    #[test]
+    fn to_f64() {
+        assert_eq!(Constant::from_i64(-3).to_f64(), -3.0);
+        assert_eq!(Constant::Float(0.5).to_f64(), 0.5);
+        assert_eq!(
+            Constant::parse_integer("18446744073709551616")
+                .unwrap()
+                .to_f64(),
+            18446744073709551616.0
+        );
+        assert_eq!(
+            Constant::parse_integer("-0x10000000000000000000000000000000")
+                .unwrap()
+                .to_f64(),
+            -(2f64.powi(124))
+        );
+    }
+
+    fn options(entries: Vec<(&str, JsValue)>) -> Result<MethodOptions, String> {
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect();
+
+        MethodOptions::parse("test", JsValue::Object(entries))
+    }
+
+    #[test]
+    fn method_options_default() {
+        for value in vec![
+            JsValue::Null,
+            JsValue::Undefined,
+            JsValue::Object(HashMap::new()),
+        ] {
+            let parsed = MethodOptions::parse("test", value).unwrap();
+
+            assert_eq!(parsed.linkage, MethodLinkage::External);
+            assert_eq!(parsed.export_name, None);
+            assert_eq!(parsed.calling_convention, CallingConvention::C);
+            assert!(parsed.attributes.is_empty());
+        }
+
+        assert!(MethodOptions::parse("test", JsValue::Bool(true)).is_err());
+        assert!(MethodOptions::parse("test", JsValue::String("internal".to_owned())).is_err());
+    }
+
+    #[test]
+    fn method_options() {
+        let parsed = options(vec![
+            ("linkage", JsValue::String("internal".to_owned())),
+            ("exportName", JsValue::String("hash".to_owned())),
+            ("callingConvention", JsValue::String("fast".to_owned())),
+            ("noinline", JsValue::Bool(true)),
+            ("cold", JsValue::Bool(false)),
+            ("noreturn", JsValue::Undefined),
+        ])
+        .unwrap();
+
+        assert_eq!(parsed.linkage, MethodLinkage::Internal);
+        assert_eq!(parsed.export_name, Some("hash".to_owned()));
+        assert_eq!(parsed.calling_convention, CallingConvention::Fast);
+        assert_eq!(parsed.attributes, vec![MethodAttribute::NoInline]);
+
+        let parsed = options(vec![
+            ("linkage", JsValue::Null),
+            ("exportName", JsValue::Undefined),
+        ])
+        .unwrap();
+
+        assert_eq!(parsed.linkage, MethodLinkage::External);
+        assert_eq!(parsed.export_name, None);
+    }
+
+    #[test]
+    fn method_options_mistyped() {
+        assert!(options(vec![("linkage", JsValue::Bool(false))]).is_err());
+        assert!(options(vec![("linkage", JsValue::String("static".to_owned()))]).is_err());
+        assert!(options(vec![("exportName", JsValue::Bool(false))]).is_err());
+        assert!(options(vec![("exportName", JsValue::Int(1))]).is_err());
+        assert!(options(vec![("callingConvention", JsValue::Bool(true))]).is_err());
+        assert!(options(vec![(
+            "callingConvention",
+            JsValue::String("stdcall".to_owned())
+        )])
+        .is_err());
+        assert!(options(vec![("noinline", JsValue::String("yes".to_owned()))]).is_err());
+        assert!(options(vec![("noinline", JsValue::Int(1))]).is_err());
+    }
+
+    #[test]
+    fn method_options_unknown() {
+        assert!(options(vec![("inline", JsValue::Bool(true))]).is_err());
+        assert!(options(vec![("inline", JsValue::Bool(false))]).is_err());
+        assert!(options(vec![("inline", JsValue::Null)]).is_err());
+    }
+
+    #[test]
+    fn method_options_conflicting() {
+        assert!(options(vec![
+            ("noinline", JsValue::Bool(true)),
+            ("alwaysinline", JsValue::Bool(true)),
+        ])
+        .is_err());
+
+        assert!(options(vec![
+            ("noinline", JsValue::Bool(true)),
+            ("alwaysinline", JsValue::Bool(false)),
+        ])
+        .is_ok());
+    }
+
+    fn export(meta: &mut JsMeta, name: &str, export_name: &str) -> Result<MethodId, String> {
+        let void = meta.add_type(TypeDefinition::Void);
+        let method_id = meta.add_method(name.to_owned(), void, vec![])?;
+
+        let options = MethodOptions {
+            export_name: Some(export_name.to_owned()),
+            ..Default::default()
+        };
+
+        meta.set_method_options(method_id, options)?;
+        Ok(method_id)
+    }
+
+    fn external(meta: &mut JsMeta, name: &str) -> Result<MethodId, String> {
+        let void = meta.add_type(TypeDefinition::Void);
+        meta.add_extern(name.to_owned(), void, vec![], false)
+    }
+
+    #[test]
+    fn symbol_collides_with_method() {
+        let mut meta = JsMeta::new();
+        external(&mut meta, "puts").unwrap();
+
+        assert!(export(&mut meta, "print", "puts").is_err());
+        assert!(external(&mut meta, "puts").is_err());
+    }
+
+    #[test]
+    fn symbol_collides_with_global() {
+        let mut meta = JsMeta::new();
+        meta.add_global("counter".to_owned(), meta.bool_type, None, true)
+            .unwrap();
+
+        assert!(export(&mut meta, "count", "tinkerlang_counter").is_err());
+        assert!(external(&mut meta, "tinkerlang_counter").is_err());
+    }
+
+    #[test]
+    fn symbol_reserved() {
+        let mut meta = JsMeta::new();
+
+        assert!(export(&mut meta, "start", "main").is_err());
+        assert!(external(&mut meta, "main").is_err());
+        assert!(external(&mut meta, "llvm.trap").is_err());
+
+        // the lowerer's own `main` is prefixed, so it doesn't clash
+        assert!(export(&mut meta, "main", "tinkerlang_main").is_ok());
+    }
+
+    #[test]
+    fn symbol_invalid() {
+        let mut meta = JsMeta::new();
+
+        assert!(export(&mut meta, "empty", "").is_err());
+        assert!(external(&mut meta, "").is_err());
+        assert!(external(&mut meta, "a\0b").is_err());
+    }
+
+    #[test]
+    fn symbol_renamed_to_itself() {
+        let mut meta = JsMeta::new();
+        let method_id = export(&mut meta, "hash", "hash").unwrap();
+
+        let options = MethodOptions {
+            export_name: Some("hash".to_owned()),
+            ..Default::default()
+        };
+
+        assert!(meta.set_method_options(method_id, options).is_ok());
+    }
+
+    #[test]
+    fn symbol_ignores_literals() {
+        let mut meta = JsMeta::new();
+        meta.add_string("hello".to_owned());
+
+        assert!(export(&mut meta, "string", "str").is_ok());
+    }
+
+    #[test]
+    fn global_collides() {
+        let mut meta = JsMeta::new();
+        let bool_type = meta.bool_type;
+        meta.add_global("counter".to_owned(), bool_type, None, true)
+            .unwrap();
+
+        assert!(meta
+            .add_global("counter".to_owned(), bool_type, None, false)
+            .is_err());
+
+        let void = meta.add_type(TypeDefinition::Void);
+        meta.add_method("hash".to_owned(), void, vec![]).unwrap();
+        assert!(meta
+            .add_global("hash".to_owned(), bool_type, None, false)
+            .is_err());
+
+        assert!(meta.add_method("counter".to_owned(), void, vec![]).is_err());
+    }
+
+    #[test]
+    fn struct_names() {
+        let mut meta = JsMeta::new();
+        let bool_type = meta.bool_type;
+        let byte = meta.add_type(TypeDefinition::Integer {
+            signed: false,
+            bits: 8,
+        });
+
+        let point = meta
+            .add_struct("Point".to_owned(), vec![byte, byte])
+            .unwrap();
+
+        // the same struct again is the same type
+        assert_eq!(
+            meta.add_struct("Point".to_owned(), vec![byte, byte]),
+            Ok(point)
+        );
+
+        assert!(meta.add_struct("Point".to_owned(), vec![byte]).is_err());
+        assert!(meta
+            .add_struct("Point".to_owned(), vec![byte, bool_type])
+            .is_err());
+
+        assert!(meta.add_struct("Pair".to_owned(), vec![byte, byte]).is_ok());
+    }
+}
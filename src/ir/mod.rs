@@ -1,7 +1,9 @@
-use self::js_boundary::JsMetaHandle;
+use self::{js_boundary::JsMetaHandle, verify::VerifyError};
 
 pub(crate) mod emit;
+pub(crate) mod intrinsics;
 pub(crate) mod js_boundary;
+pub(crate) mod verify;
 
 pub struct IrBuilder {
     meta: JsMetaHandle,
@@ -13,11 +15,19 @@ impl IrBuilder {
         Self { meta }
     }
 
+    /// Checks the IR produced by the lowerer, which must have no errors to be
+    /// hydrated.
+    pub fn verify(&self) -> Vec<VerifyError> {
+        let meta = self.meta.lock().unwrap();
+        verify::verify(&meta)
+    }
+
+    /// Emits the IR into an LLVM module, failing if LLVM rejects it.
     pub fn hydrate<'ctx>(
         &self,
         context: &'ctx inkwell::context::Context,
         module: &mut inkwell::module::Module<'ctx>,
-    ) {
+    ) -> Result<(), String> {
         emit::hydrate(&self.meta, context, module)
     }
 }
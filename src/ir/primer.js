@@ -13,6 +13,11 @@
  * @typedef {object} TreeNode
  * @property {string} type
  * @property {unknown} value
+ * @property {boolean} isError whether tree-sitter couldn't parse the node
+ * @property {boolean} isMissing whether tree-sitter inserted the node to
+ * recover from a syntax error
+ * @property {boolean} hasError whether the node or any of its children is an
+ * error or missing
  * @property {Node[]} children
  */
 
@@ -29,6 +34,30 @@ var toValue;
 /** @type {CompilerType} */
 var __compiler_type;
 
+/** @typedef {(size: number) => TypeId} CompilerFloatType */
+/** @type {CompilerFloatType} */
+var __compiler_float_type;
+
+/** @typedef {() => TypeId} CompilerVoidType */
+/** @type {CompilerVoidType} */
+var __compiler_void_type;
+
+/** @typedef {(pointee: TypeId) => TypeId} CompilerPointerType */
+/** @type {CompilerPointerType} */
+var __compiler_pointer_type;
+
+/** @typedef {(returnType: TypeId, parameters: TypeId[], variadic: boolean) => TypeId} CompilerFunctionPointerType */
+/** @type {CompilerFunctionPointerType} */
+var __compiler_function_pointer_type;
+
+/** @typedef {(name: string, fields: TypeId[]) => TypeId} CompilerStructType */
+/** @type {CompilerStructType} */
+var __compiler_struct_type;
+
+/** @typedef {(element: TypeId, length: number) => TypeId} CompilerArrayType */
+/** @type {CompilerArrayType} */
+var __compiler_array_type;
+
 /** @typedef {{__methodid_FAKE_FOR_SAKE_OF_TYPES: unknown}} MethodId */
 
 /** @typedef {(method_id: MethodId) => void} CompilerSetMain */
@@ -39,21 +68,79 @@ var __compiler_set_main;
 /** @type {CompilerGenerateMethod} */
 var __compiler_generate_method;
 
+/**
+ * @typedef {object} MethodOptions
+ * @property {"external" | "internal" | "weak"} [linkage]
+ * @property {string} [exportName] the symbol to export the method as, instead
+ * of `tinkerlang_<name>`
+ * @property {"c" | "fast" | "cold"} [callingConvention]
+ * @property {boolean} [noinline]
+ * @property {boolean} [alwaysinline]
+ * @property {boolean} [noreturn]
+ * @property {boolean} [cold]
+ */
+
+/** @typedef {(method_id: MethodId, options: MethodOptions) => void} CompilerSetMethodOptions */
+/** @type {CompilerSetMethodOptions} */
+var __compiler_set_method_options;
+
+/** @typedef {(name: string, return_type: TypeId, parameters: TypeId[], variadic: boolean) => MethodId} CompilerGenerateExtern */
+/** @type {CompilerGenerateExtern} */
+var __compiler_generate_extern;
+
 /** @typedef {{__blockid_FAKE_FOR_SAKE_OF_TYPES: unknown}} BlockId */
 
 /** @typedef {(methodId: MethodId, name: string) => BlockId} CompilerGenerateBlock */
 /** @type {CompilerGenerateBlock} */
 var __compiler_generate_block;
 
+/** @typedef {number} LocalId */
+
+/** @typedef {(methodId: MethodId, type: TypeId) => LocalId} CompilerGenerateLocal */
+/** @type {CompilerGenerateLocal} */
+var __compiler_generate_local;
+
+/** @typedef {number} GlobalId */
+
+/** @typedef {(name: string, type: TypeId, initializer: number | string | null, mutable: boolean) => GlobalId} CompilerGenerateGlobal */
+/** @type {CompilerGenerateGlobal} */
+var __compiler_generate_global;
+
+/** @typedef {(value: string) => GlobalId} CompilerGenerateString */
+/** @type {CompilerGenerateString} */
+var __compiler_generate_string;
+
 /** @typedef {(methodId: MethodId, blockId: BlockId, instruction: string, values: any[])} CompilerEmit */
 /** @type {CompilerEmit} */
 var __compiler_emit;
 
 /** @typedef {number} Register */
 
+/** @typedef {(level: "error" | "warning" | "note", value: unknown, message: string) => void} CompilerDiagnostic */
+/** @type {CompilerDiagnostic} */
+var __compiler_diagnostic;
+
 //===}
 
-const { context, bool, i8, i16, i32, i64, u8, u16, u32, u64 } = (() => {
+// The builtins report bad IR by throwing a message. Rethrow it as an `Error`,
+// so that its stack trace points to the line of the lowerer that caused it.
+for (const name of Object.getOwnPropertyNames(globalThis)) {
+    if (!name.startsWith("__compiler_")) {
+        continue;
+    }
+
+    const builtin = globalThis[name];
+    globalThis[name] = (...args) => {
+        try {
+            return builtin(...args);
+        } catch (error) {
+            throw error instanceof Error ? error : new Error(String(error));
+        }
+    };
+}
+
+// `void` is a keyword, so the void type is exposed as `void_`
+const { context, ptr, array, fn_ptr, void_, bool, i8, i16, i32, i64, u8, u16, u32, u64, f32, f64 } = (() => {
     class Block {
         /**
          * @param {MethodId} methodId
@@ -75,6 +162,282 @@ const { context, bool, i8, i16, i32, i64, u8, u16, u32, u64 } = (() => {
             return this;
         }
 
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        sub(rResult, rA, rB) {
+            this.emit("sub", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        mul(rResult, rA, rB) {
+            this.emit("mul", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Signed or unsigned division, depending on the type of `rA`.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        div(rResult, rA, rB) {
+            this.emit("div", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Signed or unsigned remainder, depending on the type of `rA`.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        rem(rResult, rA, rB) {
+            this.emit("rem", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rSource
+         */
+        neg(rResult, rSource) {
+            this.emit("neg", [rResult, rSource]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        and(rResult, rA, rB) {
+            this.emit("and", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        or(rResult, rA, rB) {
+            this.emit("or", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        xor(rResult, rA, rB) {
+            this.emit("xor", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rSource
+         */
+        not(rResult, rSource) {
+            this.emit("not", [rResult, rSource]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        shl(rResult, rA, rB) {
+            this.emit("shl", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Arithmetic or logical shift right, depending on whether the type of
+         * `rA` is signed.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        shr(rResult, rA, rB) {
+            this.emit("shr", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        fadd(rResult, rA, rB) {
+            this.emit("fadd", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        fsub(rResult, rA, rB) {
+            this.emit("fsub", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        fmul(rResult, rA, rB) {
+            this.emit("fmul", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        fdiv(rResult, rA, rB) {
+            this.emit("fdiv", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        frem(rResult, rA, rB) {
+            this.emit("frem", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rSource
+         */
+        fneg(rResult, rSource) {
+            this.emit("fneg", [rResult, rSource]);
+            return this;
+        }
+
+        /**
+         * Produces a `bool` by comparing two floats. The predicate is one of
+         * LLVM's: `oeq`, `one`, `olt`, `ole`, `ogt`, `oge` are false if either
+         * operand is NaN, `ueq`, `une`, `ult`, `ule`, `ugt`, `uge` are true if
+         * either operand is NaN, and `ord`/`uno` check for NaN.
+         * @param {Register} rResult
+         * @param {string} predicate
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        fcmp(rResult, predicate, rA, rB) {
+            this.emit("fcmp", [rResult, predicate, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Converts an integer to a float, treating it as signed or unsigned
+         * depending on the type of `rSource`.
+         * @param {Register} rResult
+         * @param {TypeId} type
+         * @param {Register} rSource
+         */
+        itof(rResult, type, rSource) {
+            this.emit("itof", [rResult, type, rSource]);
+            return this;
+        }
+
+        /**
+         * Converts a float to an integer, rounding towards zero. Signedness
+         * is taken from `type`.
+         * @param {Register} rResult
+         * @param {TypeId} type
+         * @param {Register} rSource
+         */
+        ftoi(rResult, type, rSource) {
+            this.emit("ftoi", [rResult, type, rSource]);
+            return this;
+        }
+
+        /**
+         * Produces a `bool` of `rA == rB`.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        eq(rResult, rA, rB) {
+            this.emit("eq", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Produces a `bool` of `rA != rB`.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        ne(rResult, rA, rB) {
+            this.emit("ne", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Produces a `bool` of `rA < rB`, compared signed or unsigned depending on the type of `rA`.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        lt(rResult, rA, rB) {
+            this.emit("lt", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Produces a `bool` of `rA <= rB`, compared signed or unsigned depending on the type of `rA`.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        le(rResult, rA, rB) {
+            this.emit("le", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Produces a `bool` of `rA > rB`, compared signed or unsigned depending on the type of `rA`.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        gt(rResult, rA, rB) {
+            this.emit("gt", [rResult, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Produces a `bool` of `rA >= rB`, compared signed or unsigned depending on the type of `rA`.
+         * @param {Register} rResult
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        ge(rResult, rA, rB) {
+            this.emit("ge", [rResult, rA, rB]);
+            return this;
+        }
+
         /**
          * @param {Register} rResult
          * @param {number} paramNum
@@ -85,17 +448,31 @@ const { context, bool, i8, i16, i32, i64, u8, u16, u32, u64 } = (() => {
         }
 
         /**
-         * @param {Registter} rResult
+         * Integers may be given as numbers, BigInts, or decimal/`0x` hex
+         * strings, and must fit within `type`. Whole numbers beyond 2^53 may
+         * already be rounded, so a warning is printed for them and a BigInt
+         * should be used instead. Floats may be given as numbers
+         * or strings such as `"inf"` and `"NaN"`.
+         * @param {Register} rResult
          * @param {TypeId} type
-         * @param {*} value
+         * @param {number | bigint | string} value
          */
         ld_const(rResult, type, value) {
+            // BigInts can't cross into the compiler, so they're passed as text
+            if (typeof value === "bigint") {
+                value = value.toString();
+            }
+
             this.emit("ld_const", [rResult, type, value]);
             return this;
         }
 
         /**
-         * @param {Register} rResult 
+         * Variadic externs may be given more parameters than they declare.
+         * These are passed as-is, so C's promotions (such as `f32` to `f64`)
+         * must be done beforehand.
+         * @param {Register | null} rResult `null` to discard the result, which
+         * is required when calling a method returning `void_`
          * @param {Method | Block} methodOrBlock 
          * @param {Register[]} rParams 
          */
@@ -105,7 +482,31 @@ const { context, bool, i8, i16, i32, i64, u8, u16, u32, u64 } = (() => {
         }
 
         /**
-         * @param {Register | undefined} rResult 
+         * Gets a pointer to a method, which has the type
+         * `fn_ptr(returnType, parameters)` of the method's signature.
+         * @param {Register} rResult
+         * @param {Method | Block} methodOrBlock
+         */
+        fn_addr(rResult, methodOrBlock) {
+            this.emit("fn_addr", [rResult, methodOrBlock.id ?? methodOrBlock.methodId]);
+            return this;
+        }
+
+        /**
+         * Calls the method that `rPointer` points to.
+         * @param {Register | null} rResult `null` to discard the result, which
+         * is required when the method returns `void_`
+         * @param {Register} rPointer
+         * @param {Register[]} rParams
+         */
+        call_indirect(rResult, rPointer, rParams) {
+            this.emit("call_indirect", [rResult, rPointer, rParams]);
+            return this;
+        }
+
+        /**
+         * @param {Register | undefined} rResult omitted when returning from a
+         * method returning `void_`
          */
         ret(rResult) {
             this.emit("ret", [rResult]);
@@ -121,6 +522,405 @@ const { context, bool, i8, i16, i32, i64, u8, u16, u32, u64 } = (() => {
             this.emit("trunc", [rResult, rType, rSource]);
             return this;
         }
+
+        /**
+         * @param {Register} rResult
+         * @param {TypeId} rType
+         * @param {Register} rSource
+         */
+        sext(rResult, rType, rSource) {
+            this.emit("sext", [rResult, rType, rSource]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {TypeId} rType
+         * @param {Register} rSource
+         */
+        zext(rResult, rType, rSource) {
+            this.emit("zext", [rResult, rType, rSource]);
+            return this;
+        }
+
+        /**
+         * Converts `rSource` into `rType`, truncating or extending as needed.
+         * Integers are sign extended when the type of `rSource` is signed,
+         * and zero extended otherwise.
+         * @param {Register} rResult
+         * @param {TypeId} rType
+         * @param {Register} rSource
+         */
+        cast(rResult, rType, rSource) {
+            this.emit("cast", [rResult, rType, rSource]);
+            return this;
+        }
+
+        /**
+         * Reinterprets the bits of `rSource` as `rType`, which must be the
+         * same size.
+         * @param {Register} rResult
+         * @param {TypeId} rType
+         * @param {Register} rSource
+         */
+        bitcast(rResult, rType, rSource) {
+            this.emit("bitcast", [rResult, rType, rSource]);
+            return this;
+        }
+
+        /**
+         * @param {Block} target
+         */
+        br(target) {
+            this.emit("br", [this.ownBlockId(target)]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rCondition
+         * @param {Block} thenBlock
+         * @param {Block} elseBlock
+         */
+        cond_br(rCondition, thenBlock, elseBlock) {
+            this.emit("cond_br", [rCondition, this.ownBlockId(thenBlock), this.ownBlockId(elseBlock)]);
+            return this;
+        }
+
+        /**
+         * Jumps to the block of the case matching `rCondition`, or to
+         * `defaultBlock` if none match. Case values are constants, given like
+         * `ld_const` takes them.
+         * @param {Register} rCondition an integer
+         * @param {Block} defaultBlock
+         * @param {[number | bigint | string, Block][]} cases
+         */
+        switch(rCondition, defaultBlock, cases) {
+            const ownCases = cases.map(([value, block]) => [
+                typeof value === "bigint" ? value.toString() : value,
+                this.ownBlockId(block),
+            ]);
+
+            this.emit("switch", [rCondition, this.ownBlockId(defaultBlock), ownCases]);
+            return this;
+        }
+
+        /**
+         * Picks `rA` if `rCondition` is true, otherwise `rB`.
+         * @param {Register} rResult
+         * @param {Register} rCondition
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        select(rResult, rCondition, rA, rB) {
+            this.emit("select", [rResult, rCondition, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Adds two integers, and writes whether the result overflowed into
+         * `rOverflow` as a `bool`. Overflow is signed or unsigned depending on
+         * the type of `rA`.
+         * @param {Register} rResult
+         * @param {Register} rOverflow
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        add_checked(rResult, rOverflow, rA, rB) {
+            this.emit("add_checked", [rResult, rOverflow, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rOverflow
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        sub_checked(rResult, rOverflow, rA, rB) {
+            this.emit("sub_checked", [rResult, rOverflow, rA, rB]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rOverflow
+         * @param {Register} rA
+         * @param {Register} rB
+         */
+        mul_checked(rResult, rOverflow, rA, rB) {
+            this.emit("mul_checked", [rResult, rOverflow, rA, rB]);
+            return this;
+        }
+
+        /**
+         * Calls an LLVM intrinsic by its short name. Available are `ctpop`,
+         * `ctlz`, `cttz`, `bswap` and `bitreverse` on integers, `sqrt`,
+         * `fabs`, `floor`, `ceil`, `trunc`, `round`, `sin`, `cos`, `exp`,
+         * `log`, `pow`, `minnum`, `maxnum` and `copysign` on floats, and
+         * `memcpy(dest, source, bytes)`, `memmove(dest, source, bytes)` and
+         * `memset(dest, byte, bytes)`, which produce no value.
+         * @param {Register | null} rResult `null` to discard the result, which
+         * is required for the memory intrinsics
+         * @param {string} name
+         * @param {Register[]} rArgs
+         */
+        intrinsic(rResult, name, rArgs) {
+            this.emit("intrinsic", [rResult, name, rArgs]);
+            return this;
+        }
+
+        /**
+         * Orderings are one of `"unordered"`, `"monotonic"`, `"acquire"`,
+         * `"release"`, `"acq_rel"` or `"seq_cst"`. Atomics work on integers
+         * that are a power of two bytes wide, floats and pointers.
+         * @param {Register} rResult
+         * @param {Register} rPointer
+         * @param {string} ordering
+         */
+        atomic_load(rResult, rPointer, ordering) {
+            this.emit("atomic_load", [rResult, rPointer, ordering]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rPointer
+         * @param {Register} rSource
+         * @param {string} ordering
+         */
+        atomic_store(rPointer, rSource, ordering) {
+            this.emit("atomic_store", [rPointer, rSource, ordering]);
+            return this;
+        }
+
+        /**
+         * Atomically applies `operation` to the integer `rPointer` points to
+         * and `rSource`, storing the result and loading the old value into
+         * `rResult`. Operations are `"xchg"`, `"add"`, `"sub"`, `"and"`,
+         * `"nand"`, `"or"`, `"xor"`, `"max"` and `"min"`. Floats can only be
+         * exchanged with `"xchg"`.
+         * @param {Register} rResult
+         * @param {string} operation
+         * @param {Register} rPointer
+         * @param {Register} rSource
+         * @param {string} ordering
+         */
+        atomic_rmw(rResult, operation, rPointer, rSource, ordering) {
+            this.emit("atomic_rmw", [rResult, operation, rPointer, rSource, ordering]);
+            return this;
+        }
+
+        /**
+         * Atomically stores `rReplacement` if the value `rPointer` points to
+         * is `rExpected`. The old value is loaded into `rResult`, and whether
+         * it was replaced into `rSuccess` as a `bool`. Floats cannot be
+         * compared and exchanged.
+         * @param {Register} rResult
+         * @param {Register} rSuccess
+         * @param {Register} rPointer
+         * @param {Register} rExpected
+         * @param {Register} rReplacement
+         * @param {string} successOrdering
+         * @param {string} failureOrdering
+         */
+        cmpxchg(rResult, rSuccess, rPointer, rExpected, rReplacement, successOrdering, failureOrdering) {
+            this.emit("cmpxchg", [rResult, rSuccess, rPointer, rExpected, rReplacement, successOrdering, failureOrdering]);
+            return this;
+        }
+
+        /**
+         * @param {string} ordering
+         */
+        fence(ordering) {
+            this.emit("fence", [ordering]);
+            return this;
+        }
+
+        /**
+         * Aborts the program. As this isn't a terminator, it's usually
+         * followed by `unreachable`.
+         */
+        trap() {
+            this.emit("trap", []);
+            return this;
+        }
+
+        /**
+         * Marks the end of a block that control never reaches.
+         */
+        unreachable() {
+            this.emit("unreachable", []);
+            return this;
+        }
+
+        /**
+         * Picks a value depending on which block control came from. Phis must
+         * come before any other instruction in a block, and give exactly one
+         * value for every block that branches to it.
+         * @param {Register} rResult
+         * @param {TypeId} type
+         * @param {[Register, Block][]} incoming
+         */
+        phi(rResult, type, incoming) {
+            this.emit("phi", [rResult, type, incoming.map(([rValue, block]) => [rValue, this.ownBlockId(block)])]);
+            return this;
+        }
+
+        /**
+         * Creates a mutable local variable, which lives for the whole method.
+         * Unlike the other instructions, this returns the local rather than
+         * the block.
+         * @param {TypeId} type
+         * @returns {LocalId}
+         */
+        local(type) {
+            return __compiler_generate_local(this.methodId, type);
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {LocalId} local
+         */
+        load(rResult, local) {
+            this.emit("load", [rResult, local]);
+            return this;
+        }
+
+        /**
+         * @param {LocalId} local
+         * @param {Register} rSource
+         */
+        store(local, rSource) {
+            this.emit("store", [local, rSource]);
+            return this;
+        }
+
+        /**
+         * Produces a pointer to a local.
+         * @param {Register} rResult
+         * @param {LocalId} local
+         */
+        addr_of(rResult, local) {
+            this.emit("addr_of", [rResult, local]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rPointer
+         */
+        load_ptr(rResult, rPointer) {
+            this.emit("load_ptr", [rResult, rPointer]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rPointer
+         * @param {Register} rSource
+         */
+        store_ptr(rPointer, rSource) {
+            this.emit("store_ptr", [rPointer, rSource]);
+            return this;
+        }
+
+        /**
+         * Offsets a pointer by `rIndex` values of the type it points to.
+         * @param {Register} rResult
+         * @param {Register} rPointer
+         * @param {Register} rIndex
+         */
+        gep(rResult, rPointer, rIndex) {
+            this.emit("gep", [rResult, rPointer, rIndex]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {TypeId} rType
+         * @param {Register} rSource
+         */
+        ptrtoint(rResult, rType, rSource) {
+            this.emit("ptrtoint", [rResult, rType, rSource]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {TypeId} rType
+         * @param {Register} rSource
+         */
+        inttoptr(rResult, rType, rSource) {
+            this.emit("inttoptr", [rResult, rType, rSource]);
+            return this;
+        }
+
+        /**
+         * Loads a value of `rType` where every bit is zero, such as an empty
+         * struct to insert fields into.
+         * @param {Register} rResult
+         * @param {TypeId} rType
+         */
+        ld_zero(rResult, rType) {
+            this.emit("ld_zero", [rResult, rType]);
+            return this;
+        }
+
+        /**
+         * @param {Register} rResult
+         * @param {Register} rAggregate a struct or array
+         * @param {number} index
+         */
+        extract_field(rResult, rAggregate, index) {
+            this.emit("extract_field", [rResult, rAggregate, index]);
+            return this;
+        }
+
+        /**
+         * Produces a copy of `rAggregate`, with the field at `index` set to
+         * `rSource`.
+         * @param {Register} rResult
+         * @param {Register} rAggregate a struct or array
+         * @param {number} index
+         * @param {Register} rSource
+         */
+        insert_field(rResult, rAggregate, index, rSource) {
+            this.emit("insert_field", [rResult, rAggregate, index, rSource]);
+            return this;
+        }
+
+        /**
+         * Gets a pointer to the field at `index` of the struct or array that
+         * `rPointer` points to.
+         * @param {Register} rResult
+         * @param {Register} rPointer
+         * @param {number} index
+         */
+        field_ptr(rResult, rPointer, index) {
+            this.emit("field_ptr", [rResult, rPointer, index]);
+            return this;
+        }
+
+        /**
+         * Gets a pointer to a global. For strings, this points to the first
+         * byte.
+         * @param {Register} rResult
+         * @param {GlobalId} global
+         */
+        global_addr(rResult, global) {
+            this.emit("global_addr", [rResult, global]);
+            return this;
+        }
+
+        /**
+         * Blocks can only be referred to from within the same method.
+         * @param {Block} block
+         */
+        ownBlockId(block) {
+            if (block.methodId !== this.methodId) {
+                throw new Error("cannot refer to a block of another method");
+            }
+
+            return block.blockId;
+        }
     }
 
     class Method {
@@ -144,9 +944,25 @@ const { context, bool, i8, i16, i32, i64, u8, u16, u32, u64 } = (() => {
          * @param {string} name
          * @param {TypeId} returnType
          * @param {TypeId[]} parameters
+         * @param {MethodOptions} options linkage, calling convention and
+         * attributes, which default to an external, prefixed, c method
+         */
+        method(name, returnType, parameters, options = {}) {
+            const id = __compiler_generate_method(name, returnType, parameters);
+            __compiler_set_method_options(id, options);
+            return new Method(id);
+        }
+
+        /**
+         * Declares a method from outside the module, such as `printf` from
+         * libc. Its name isn't prefixed, and it can't have blocks.
+         * @param {string} name
+         * @param {TypeId} returnType
+         * @param {TypeId[]} parameters
+         * @param {{ variadic?: boolean }} options
          */
-        method(name, returnType, parameters) {
-            return new Method(__compiler_generate_method(name, returnType, parameters));
+        extern(name, returnType, parameters, { variadic = false } = {}) {
+            return new Method(__compiler_generate_extern(name, returnType, parameters, variadic));
         }
 
         /**
@@ -155,10 +971,111 @@ const { context, bool, i8, i16, i32, i64, u8, u16, u32, u64 } = (() => {
         setMain(methodOrBlock) {
             __compiler_set_main(methodOrBlock.id ?? methodOrBlock.methodId);
         }
+
+        /**
+         * A named record of fields, laid out in order. Calling this again with
+         * the same name and fields gives the same type, but another struct
+         * can't use the name. Structs can't refer to themselves, as their
+         * fields must already exist.
+         * @param {string} name
+         * @param {TypeId[]} fields
+         * @returns {TypeId}
+         */
+        struct(name, fields) {
+            return __compiler_struct_type(name, fields);
+        }
+
+        /**
+         * A module-level variable, read and written through `global_addr`. Its
+         * name can't be the same as another global or method.
+         * @param {string} name
+         * @param {TypeId} type
+         * @param {number | bigint | string | null} initializer a number
+         * constant, like `ld_const` takes, or `null` for all zeroes
+         * @param {{ mutable?: boolean }} options globals are immutable unless
+         * `mutable` is set
+         * @returns {GlobalId}
+         */
+        global(name, type, initializer, { mutable = false } = {}) {
+            if (typeof initializer === "bigint") {
+                initializer = initializer.toString();
+            }
+
+            return __compiler_generate_global(name, type, initializer ?? null, mutable);
+        }
+
+        /**
+         * An immutable, nul terminated string literal. This returns a handle
+         * rather than a pointer, which `global_addr` loads as a `ptr(u8)`.
+         * @param {string} value
+         * @returns {GlobalId}
+         */
+        string(value) {
+            return __compiler_generate_string(value);
+        }
+
+        /**
+         * Reports an error in the input at `node` to the end user. The
+         * lowerer keeps running, so that it can report more than one, but
+         * nothing is compiled once it finishes.
+         * @param {TreeNode} node
+         * @param {string} message
+         */
+        error(node, message) {
+            __compiler_diagnostic("error", node.value, message);
+        }
+
+        /**
+         * Reports a warning in the input at `node` to the end user.
+         * @param {TreeNode} node
+         * @param {string} message
+         */
+        warning(node, message) {
+            __compiler_diagnostic("warning", node.value, message);
+        }
+
+        /**
+         * Reports a note about the input at `node` to the end user, such as
+         * where something in an earlier error was defined.
+         * @param {TreeNode} node
+         * @param {string} message
+         */
+        note(node, message) {
+            __compiler_diagnostic("note", node.value, message);
+        }
     }();
 
+    /**
+     * A pointer to a value of `pointee`. `ptr(void_)` is a pointer to bytes.
+     * @param {TypeId} pointee
+     * @returns {TypeId}
+     */
+    const ptr = (pointee) => __compiler_pointer_type(pointee);
+
+    /**
+     * `length` values of `element`, laid out contiguously.
+     * @param {TypeId} element
+     * @param {number} length
+     * @returns {TypeId}
+     */
+    const array = (element, length) => __compiler_array_type(element, length);
+
+    /**
+     * A pointer to a method with the given signature.
+     * @param {TypeId} returnType
+     * @param {TypeId[]} parameters
+     * @param {{ variadic?: boolean }} options
+     * @returns {TypeId}
+     */
+    const fn_ptr = (returnType, parameters, { variadic = false } = {}) =>
+        __compiler_function_pointer_type(returnType, parameters, variadic);
+
     return {
         context,
+        ptr,
+        array,
+        fn_ptr,
+        void_: __compiler_void_type(),
         bool: __compiler_type(false, 1),
         i8: __compiler_type(true, 8),
         u8: __compiler_type(false, 8),
@@ -168,5 +1085,7 @@ const { context, bool, i8, i16, i32, i64, u8, u16, u32, u64 } = (() => {
         u16: __compiler_type(false, 16),
         u32: __compiler_type(false, 32),
         u64: __compiler_type(false, 64),
+        f32: __compiler_float_type(32),
+        f64: __compiler_float_type(64),
     };
 })(this);
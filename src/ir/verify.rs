@@ -0,0 +1,1577 @@
+use std::{collections::HashMap, fmt};
+
+use crate::ir::intrinsics::IntrinsicKind;
+use crate::ir::js_boundary::{
+    AtomicOperation, BlockId, CallingConvention, Instruction, JsMeta, MethodDefinition, MethodId,
+    Register, TypeDefinition, TypeId,
+};
+
+/// The control flow graph of a method, along with the dominator of every
+/// block. The first block of a method is its entry.
+pub struct ControlFlow {
+    predecessors: Vec<Vec<BlockId>>,
+    reachable: Vec<bool>,
+    order: Vec<BlockId>,
+    dominators: Vec<Vec<bool>>,
+}
+
+impl ControlFlow {
+    pub fn new(method: &MethodDefinition) -> Self {
+        let count = method.blocks.len();
+
+        let successors = method
+            .blocks
+            .iter()
+            .map(|block| {
+                block
+                    .instructions
+                    .iter()
+                    .flat_map(Instruction::successors)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut predecessors = vec![vec![]; count];
+        for (block_id, targets) in successors.iter().enumerate() {
+            for target in targets.iter() {
+                predecessors[*target as usize].push(block_id as BlockId);
+            }
+        }
+
+        // depth first search from the entry, to find the reverse postorder
+        let mut reachable = vec![false; count];
+        let mut postorder = Vec::new();
+        let mut stack = Vec::new();
+
+        if count > 0 {
+            reachable[0] = true;
+            stack.push((0, 0));
+        }
+
+        while let Some((block_id, next)) = stack.pop() {
+            match successors[block_id].get(next) {
+                Some(target) => {
+                    stack.push((block_id, next + 1));
+
+                    let target = *target as usize;
+                    if !reachable[target] {
+                        reachable[target] = true;
+                        stack.push((target, 0));
+                    }
+                }
+                None => postorder.push(block_id as BlockId),
+            }
+        }
+
+        let order = postorder.into_iter().rev().collect::<Vec<_>>();
+
+        // a block is dominated by itself, and by every block which dominates
+        // all of its predecessors. unreachable blocks are only dominated by
+        // themselves.
+        let mut dominators = (0..count)
+            .map(|block_id| {
+                if block_id == 0 || !reachable[block_id] {
+                    let mut only_self = vec![false; count];
+                    only_self[block_id] = true;
+                    only_self
+                } else {
+                    vec![true; count]
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for block_id in order.iter().skip(1).map(|id| *id as usize) {
+                let mut dominated_by = vec![true; count];
+
+                for predecessor in predecessors[block_id].iter().map(|id| *id as usize) {
+                    if !reachable[predecessor] {
+                        continue;
+                    }
+
+                    for (bit, predecessor_bit) in
+                        dominated_by.iter_mut().zip(dominators[predecessor].iter())
+                    {
+                        *bit &= *predecessor_bit;
+                    }
+                }
+
+                dominated_by[block_id] = true;
+
+                if dominated_by != dominators[block_id] {
+                    dominators[block_id] = dominated_by;
+                    changed = true;
+                }
+            }
+        }
+
+        ControlFlow {
+            predecessors,
+            reachable,
+            order,
+            dominators,
+        }
+    }
+
+    /// Whether every path from the entry to `block` passes through
+    /// `dominator`. A block always dominates itself.
+    pub fn dominates(&self, dominator: BlockId, block: BlockId) -> bool {
+        self.dominators[block as usize][dominator as usize]
+    }
+
+    /// The blocks that branch to `block`, in order. A block that branches to
+    /// it more than once, such as a `cond_br` with the same target twice,
+    /// appears once for every edge.
+    pub fn predecessors(&self, block: BlockId) -> &[BlockId] {
+        &self.predecessors[block as usize]
+    }
+
+    /// Every block, ordered such that a block is only visited after all of
+    /// the blocks that dominate it. Unreachable blocks come last.
+    pub fn emission_order(&self) -> Vec<BlockId> {
+        let unreachable = self
+            .reachable
+            .iter()
+            .enumerate()
+            .filter(|(_, reachable)| !**reachable)
+            .map(|(block_id, _)| block_id as BlockId);
+
+        self.order.iter().copied().chain(unreachable).collect()
+    }
+}
+
+/// A problem with the IR a lowerer produced, and where it was found.
+#[derive(Debug, Clone)]
+pub struct VerifyError {
+    pub method: Option<String>,
+    pub block: Option<String>,
+    pub kind: VerifyErrorKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum VerifyErrorKind {
+    NoMain,
+    ExternalMain,
+    MainTakesParameters,
+    /// `main` must return `void` or a 32 bit integer, which becomes the exit
+    /// code of the program.
+    MainReturnType {
+        found: String,
+    },
+    /// A method defined in the module needs an entry block.
+    NoBlocks,
+    MissingTerminator,
+    MultipleTerminators {
+        count: usize,
+    },
+    MisplacedPhi,
+    /// The entry block can't have predecessors, as LLVM enters it exactly
+    /// once.
+    BranchToEntry {
+        instruction: &'static str,
+    },
+    RedefinedRegister {
+        register: Register,
+        first_block: String,
+    },
+    UndefinedRegister {
+        register: Register,
+    },
+    UnavailableRegister {
+        register: Register,
+        defined_in: String,
+    },
+    PhiFromNonPredecessor {
+        predecessor: String,
+    },
+    PhiValueUnavailable {
+        register: Register,
+        predecessor: String,
+    },
+    PhiMissingPredecessor {
+        predecessor: String,
+    },
+    PhiDuplicatePredecessor {
+        predecessor: String,
+    },
+    NoSuchType {
+        type_id: TypeId,
+    },
+    NoSuchMethod {
+        method_id: MethodId,
+    },
+    ParameterOutOfBounds {
+        parameter: i32,
+        parameters: usize,
+    },
+    OperandType {
+        instruction: &'static str,
+        register: Register,
+        expected: String,
+        found: String,
+    },
+    VoidValue {
+        instruction: &'static str,
+    },
+    InvalidConstant {
+        message: String,
+    },
+    InvalidConversion {
+        instruction: &'static str,
+        from: String,
+        to: String,
+    },
+    NoSuchField {
+        instruction: &'static str,
+        index: u32,
+        aggregate: String,
+    },
+    ReturnType {
+        expected: String,
+        found: String,
+    },
+    DuplicateSwitchCase {
+        case: String,
+    },
+    CallArity {
+        callee: String,
+        expected: usize,
+        found: usize,
+        variadic: bool,
+    },
+    VoidResult {
+        callee: String,
+    },
+    /// Function pointers are always called with the C calling convention.
+    AddressOfNonCMethod {
+        method: String,
+    },
+    UnknownIntrinsic {
+        name: String,
+    },
+    IntrinsicArity {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// `bswap` swaps whole bytes, so its operand must be an even number of
+    /// them.
+    IntrinsicWidth {
+        name: String,
+        bits: u32,
+    },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.method, &self.block) {
+            (Some(method), Some(block)) => {
+                write!(f, "in block `{}` of method `{}`: ", block, method)?
+            }
+            (Some(method), None) => write!(f, "in method `{}`: ", method)?,
+            _ => {}
+        }
+
+        match &self.kind {
+            VerifyErrorKind::NoMain => write!(f, "no main method was set"),
+            VerifyErrorKind::ExternalMain => write!(f, "an external method cannot be main"),
+            VerifyErrorKind::MainTakesParameters => write!(f, "main cannot take parameters"),
+            VerifyErrorKind::MainReturnType { found } => write!(
+                f,
+                "main must return void or a 32 bit integer, not {}",
+                found
+            ),
+            VerifyErrorKind::NoBlocks => write!(
+                f,
+                "method has no blocks, but only external methods can have no body"
+            ),
+            VerifyErrorKind::MissingTerminator => write!(
+                f,
+                "block must end with a terminator (ret, br, cond_br, switch or unreachable)"
+            ),
+            VerifyErrorKind::MultipleTerminators { count } => write!(
+                f,
+                "block has {} terminators, but may only have one at its end",
+                count
+            ),
+            VerifyErrorKind::MisplacedPhi => write!(
+                f,
+                "phi instructions must come before all other instructions"
+            ),
+            VerifyErrorKind::BranchToEntry { instruction } => write!(
+                f,
+                "{} cannot branch to the first block of a method, which is its entry",
+                instruction
+            ),
+            VerifyErrorKind::RedefinedRegister {
+                register,
+                first_block,
+            } => write!(
+                f,
+                "register {} is defined more than once, and was first defined in `{}`",
+                register, first_block
+            ),
+            VerifyErrorKind::UndefinedRegister { register } => {
+                write!(f, "register {} is used but never defined", register)
+            }
+            VerifyErrorKind::UnavailableRegister {
+                register,
+                defined_in,
+            } => write!(
+                f,
+                "register {} is used, but its definition in `{}` does not dominate the use",
+                register, defined_in
+            ),
+            VerifyErrorKind::PhiFromNonPredecessor { predecessor } => write!(
+                f,
+                "phi has an incoming value from `{}`, which does not branch to it",
+                predecessor
+            ),
+            VerifyErrorKind::PhiValueUnavailable {
+                register,
+                predecessor,
+            } => write!(
+                f,
+                "register {} flows into a phi from `{}`, but is not available at the end of `{}`",
+                register, predecessor, predecessor
+            ),
+            VerifyErrorKind::PhiMissingPredecessor { predecessor } => write!(
+                f,
+                "phi has no incoming value from `{}`, which branches to it",
+                predecessor
+            ),
+            VerifyErrorKind::PhiDuplicatePredecessor { predecessor } => write!(
+                f,
+                "phi has more than one incoming value from `{}`",
+                predecessor
+            ),
+            VerifyErrorKind::NoSuchType { type_id } => write!(f, "type {} does not exist", type_id),
+            VerifyErrorKind::NoSuchMethod { method_id } => {
+                write!(f, "method {} does not exist", method_id)
+            }
+            VerifyErrorKind::ParameterOutOfBounds {
+                parameter,
+                parameters,
+            } => write!(
+                f,
+                "ld_param loads parameter {}, but the method only has {}",
+                parameter, parameters
+            ),
+            VerifyErrorKind::OperandType {
+                instruction,
+                register,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} expects register {} to be {}, but it is {}",
+                instruction, register, expected, found
+            ),
+            VerifyErrorKind::VoidValue { instruction } => {
+                write!(f, "{} cannot produce a value of type void", instruction)
+            }
+            VerifyErrorKind::InvalidConstant { message } => write!(f, "{}", message),
+            VerifyErrorKind::InvalidConversion {
+                instruction,
+                from,
+                to,
+            } => write!(f, "cannot {} from {} into {}", instruction, from, to),
+            VerifyErrorKind::NoSuchField {
+                instruction,
+                index,
+                aggregate,
+            } => write!(
+                f,
+                "{} refers to field {} of {}, which has no such field",
+                instruction, index, aggregate
+            ),
+            VerifyErrorKind::ReturnType { expected, found } => {
+                write!(f, "ret must return {}, but returns {}", expected, found)
+            }
+            VerifyErrorKind::DuplicateSwitchCase { case } => {
+                write!(f, "switch has more than one case for {}", case)
+            }
+            VerifyErrorKind::CallArity {
+                callee,
+                expected,
+                found,
+                variadic,
+            } => write!(
+                f,
+                "`{}` takes {}{} parameters, but was given {}",
+                callee,
+                if *variadic { "at least " } else { "" },
+                expected,
+                found
+            ),
+            VerifyErrorKind::VoidResult { callee } => write!(
+                f,
+                "`{}` returns void, so its result cannot be stored in a register",
+                callee
+            ),
+            VerifyErrorKind::AddressOfNonCMethod { method } => write!(
+                f,
+                "the address of method `{}` cannot be taken, as it doesn't use the c calling convention",
+                method
+            ),
+            VerifyErrorKind::UnknownIntrinsic { name } => {
+                write!(f, "there is no intrinsic called `{}`", name)
+            }
+            VerifyErrorKind::IntrinsicArity {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "intrinsic `{}` takes {} arguments, but was given {}",
+                name, expected, found
+            ),
+            VerifyErrorKind::IntrinsicWidth { name, bits } => write!(
+                f,
+                "intrinsic `{}` takes an integer with a multiple of 16 bits, not {}",
+                name, bits
+            ),
+        }
+    }
+}
+
+/// Checks the IR of every method, returning every problem found. Hydrating
+/// IR that has any problems is not possible.
+pub fn verify(meta: &JsMeta) -> Vec<VerifyError> {
+    let mut errors = Vec::new();
+
+    verify_main(meta, &mut errors);
+
+    for method in meta.methods.iter().filter(|method| !method.external) {
+        if method.blocks.is_empty() {
+            errors.push(VerifyError {
+                method: Some(method.name.clone()),
+                block: None,
+                kind: VerifyErrorKind::NoBlocks,
+            });
+            continue;
+        }
+
+        let error_count = errors.len();
+
+        verify_terminators(method, &mut errors);
+
+        let control_flow = ControlFlow::new(method);
+        verify_registers(method, &control_flow, &mut errors);
+
+        // types can only be worked out once every register has a single,
+        // available definition
+        if errors.len() == error_count {
+            check_types(meta, method, &control_flow, &mut errors);
+        }
+    }
+
+    errors
+}
+
+fn verify_main(meta: &JsMeta, errors: &mut Vec<VerifyError>) {
+    let error = |method: &MethodDefinition, kind| VerifyError {
+        method: Some(method.name.clone()),
+        block: None,
+        kind,
+    };
+
+    let main = match meta.main_id {
+        Some(main_id) => &meta.methods[main_id as usize],
+        None => {
+            errors.push(VerifyError {
+                method: None,
+                block: None,
+                kind: VerifyErrorKind::NoMain,
+            });
+            return;
+        }
+    };
+
+    if main.external {
+        errors.push(error(main, VerifyErrorKind::ExternalMain));
+    }
+
+    if !main.parameters.is_empty() {
+        errors.push(error(main, VerifyErrorKind::MainTakesParameters));
+    }
+
+    match meta.get_type(main.return_type) {
+        TypeDefinition::Void | TypeDefinition::Integer { bits: 32, .. } => {}
+        _ => errors.push(error(
+            main,
+            VerifyErrorKind::MainReturnType {
+                found: meta.type_name(main.return_type),
+            },
+        )),
+    }
+}
+
+/// Ensures that every block ends with a terminator, such as `ret` or `br`, that
+/// nothing comes after it, and that nothing branches back to the entry.
+fn verify_terminators(method: &MethodDefinition, errors: &mut Vec<VerifyError>) {
+    for block in method.blocks.iter() {
+        let error = |kind| VerifyError {
+            method: Some(method.name.clone()),
+            block: Some(block.name.clone()),
+            kind,
+        };
+
+        match block.instructions.last() {
+            Some(last) if last.is_terminator() => {}
+            _ => errors.push(error(VerifyErrorKind::MissingTerminator)),
+        }
+
+        let count = block
+            .instructions
+            .iter()
+            .filter(|inst| inst.is_terminator())
+            .count();
+
+        if count > 1 {
+            errors.push(error(VerifyErrorKind::MultipleTerminators { count }));
+        }
+
+        for inst in block.instructions.iter() {
+            if inst.successors().contains(&0) {
+                errors.push(error(VerifyErrorKind::BranchToEntry {
+                    instruction: inst.name(),
+                }));
+            }
+        }
+    }
+}
+
+/// Ensures that every register of a method is defined exactly once, that
+/// every use of a register is dominated by its definition, and that `phi`s
+/// only appear at the start of a block.
+fn verify_registers(
+    method: &MethodDefinition,
+    control_flow: &ControlFlow,
+    errors: &mut Vec<VerifyError>,
+) {
+    let block_name = |block_id: BlockId| method.blocks[block_id as usize].name.clone();
+
+    // the block and instruction index that each register is defined at
+    let mut definitions: HashMap<Register, (BlockId, usize)> = HashMap::new();
+
+    for (block_id, block) in method.blocks.iter().enumerate() {
+        for (index, inst) in block.instructions.iter().enumerate() {
+            for register in inst.results() {
+                if let Some((first, _)) = definitions.get(&register) {
+                    errors.push(VerifyError {
+                        method: Some(method.name.clone()),
+                        block: Some(block.name.clone()),
+                        kind: VerifyErrorKind::RedefinedRegister {
+                            register,
+                            first_block: block_name(*first),
+                        },
+                    });
+                } else {
+                    definitions.insert(register, (block_id as BlockId, index));
+                }
+            }
+        }
+    }
+
+    for (block_id, block) in method.blocks.iter().enumerate() {
+        let block_id = block_id as BlockId;
+        let mut past_phis = false;
+
+        let mut error = |kind| {
+            errors.push(VerifyError {
+                method: Some(method.name.clone()),
+                block: Some(block.name.clone()),
+                kind,
+            })
+        };
+
+        for (index, inst) in block.instructions.iter().enumerate() {
+            if let Instruction::Phi { incoming, .. } = inst {
+                if past_phis {
+                    error(VerifyErrorKind::MisplacedPhi);
+                }
+
+                let predecessors = control_flow.predecessors(block_id);
+                let mut seen = Vec::with_capacity(incoming.len());
+
+                for (register, predecessor) in incoming.iter() {
+                    if seen.contains(predecessor) {
+                        error(VerifyErrorKind::PhiDuplicatePredecessor {
+                            predecessor: block_name(*predecessor),
+                        });
+                        continue;
+                    }
+                    seen.push(*predecessor);
+
+                    if !predecessors.contains(predecessor) {
+                        error(VerifyErrorKind::PhiFromNonPredecessor {
+                            predecessor: block_name(*predecessor),
+                        });
+                        continue;
+                    }
+
+                    match definitions.get(register) {
+                        None => error(VerifyErrorKind::UndefinedRegister {
+                            register: *register,
+                        }),
+                        Some((defined_in, _))
+                            if !control_flow.dominates(*defined_in, *predecessor) =>
+                        {
+                            error(VerifyErrorKind::PhiValueUnavailable {
+                                register: *register,
+                                predecessor: block_name(*predecessor),
+                            })
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                // a block that branches here more than once still only gives
+                // a single incoming value
+                let mut missing = predecessors
+                    .iter()
+                    .filter(|predecessor| !seen.contains(predecessor))
+                    .copied()
+                    .collect::<Vec<_>>();
+                missing.dedup();
+
+                for predecessor in missing {
+                    error(VerifyErrorKind::PhiMissingPredecessor {
+                        predecessor: block_name(predecessor),
+                    });
+                }
+            } else {
+                past_phis = true;
+            }
+
+            for register in inst.operands() {
+                let (defined_in, defined_at) = match definitions.get(&register) {
+                    Some(definition) => *definition,
+                    None => {
+                        error(VerifyErrorKind::UndefinedRegister { register });
+                        continue;
+                    }
+                };
+
+                let available = if defined_in == block_id {
+                    defined_at < index
+                } else {
+                    control_flow.dominates(defined_in, block_id)
+                };
+
+                if !available {
+                    error(VerifyErrorKind::UnavailableRegister {
+                        register,
+                        defined_in: block_name(defined_in),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Works out the type of every register in a method, from the instruction
+/// that defines it. The method must already have been verified.
+pub fn register_types(
+    meta: &JsMeta,
+    method: &MethodDefinition,
+    control_flow: &ControlFlow,
+) -> HashMap<Register, TypeId> {
+    let mut errors = Vec::new();
+    let types = check_types(meta, method, control_flow, &mut errors);
+
+    if let Some(error) = errors.first() {
+        panic!("expected verified IR, but {}", error);
+    }
+
+    types
+}
+
+/// Why an instruction's types could not be checked.
+enum Fail {
+    /// An operand has no type, as its definition already failed to check.
+    Unknown,
+    Error(VerifyErrorKind),
+}
+
+impl From<VerifyErrorKind> for Fail {
+    fn from(kind: VerifyErrorKind) -> Self {
+        Fail::Error(kind)
+    }
+}
+
+fn is_integer(definition: &TypeDefinition) -> bool {
+    matches!(definition, TypeDefinition::Integer { .. })
+}
+
+fn is_float(definition: &TypeDefinition) -> bool {
+    matches!(definition, TypeDefinition::Float { .. })
+}
+
+fn is_pointer(definition: &TypeDefinition) -> bool {
+    matches!(definition, TypeDefinition::Pointer { .. })
+}
+
+/// Whether the type is an integer or a float, which have a size in bits.
+fn is_sized(definition: &TypeDefinition) -> bool {
+    is_integer(definition) || is_float(definition)
+}
+
+/// Whether the type is an integer LLVM can access atomically, which must be a
+/// power of two bytes wide.
+fn is_atomic_integer(definition: &TypeDefinition) -> bool {
+    is_integer(definition) && definition.bits() >= 8 && definition.bits().is_power_of_two()
+}
+
+/// Whether the type can be atomically loaded and stored.
+fn is_atomic_value(definition: &TypeDefinition) -> bool {
+    is_atomic_integer(definition) || is_float(definition) || is_any_pointer(definition)
+}
+
+fn is_any_pointer(definition: &TypeDefinition) -> bool {
+    matches!(
+        definition,
+        TypeDefinition::Pointer { .. } | TypeDefinition::FunctionPointer { .. }
+    )
+}
+
+/// Checks that the operands of every instruction have the types it expects,
+/// returning the type of every register that could be worked out.
+fn check_types(
+    meta: &JsMeta,
+    method: &MethodDefinition,
+    control_flow: &ControlFlow,
+    errors: &mut Vec<VerifyError>,
+) -> HashMap<Register, TypeId> {
+    let mut types: HashMap<Register, TypeId> = HashMap::new();
+
+    // blocks are checked in dominance order, so that the operands of an
+    // instruction are always typed before it
+    for block_id in control_flow.emission_order() {
+        let block = &method.blocks[block_id as usize];
+
+        for inst in block.instructions.iter() {
+            let checker = TypeChecker {
+                meta,
+                method,
+                types: &types,
+                instruction: inst.name(),
+            };
+
+            match checker.instruction_type(inst) {
+                Ok(type_id) => {
+                    let mut results = inst.results().into_iter();
+
+                    if let (Some(result), Some(type_id)) = (results.next(), type_id) {
+                        types.insert(result, type_id);
+                    }
+
+                    // the only secondary results are flags, such as whether
+                    // checked arithmetic overflowed
+                    for result in results {
+                        types.insert(result, meta.bool_type);
+                    }
+                }
+                Err(Fail::Unknown) => {}
+                Err(Fail::Error(kind)) => errors.push(VerifyError {
+                    method: Some(method.name.clone()),
+                    block: Some(block.name.clone()),
+                    kind,
+                }),
+            }
+        }
+    }
+
+    // the incoming values of phis may be defined after the phi itself, so
+    // they're checked once every register is typed
+    for block in method.blocks.iter() {
+        for inst in block.instructions.iter() {
+            if let Instruction::Phi {
+                type_id, incoming, ..
+            } = inst
+            {
+                let checker = TypeChecker {
+                    meta,
+                    method,
+                    types: &types,
+                    instruction: inst.name(),
+                };
+
+                for (register, _) in incoming.iter() {
+                    if let Err(Fail::Error(kind)) = checker.expect_type(*register, *type_id) {
+                        errors.push(VerifyError {
+                            method: Some(method.name.clone()),
+                            block: Some(block.name.clone()),
+                            kind,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    types
+}
+
+struct TypeChecker<'a> {
+    meta: &'a JsMeta,
+    method: &'a MethodDefinition,
+    types: &'a HashMap<Register, TypeId>,
+    instruction: &'static str,
+}
+
+impl<'a> TypeChecker<'a> {
+    fn definition(&self, type_id: TypeId) -> &'a TypeDefinition {
+        &self.meta.types[type_id as usize]
+    }
+
+    fn name(&self, type_id: TypeId) -> String {
+        self.meta.type_name(type_id)
+    }
+
+    /// Ensures a type id given directly to an instruction refers to a type.
+    fn type_exists(&self, type_id: TypeId) -> Result<TypeId, Fail> {
+        if type_id < 0 || type_id as usize >= self.meta.types.len() {
+            return Err(VerifyErrorKind::NoSuchType { type_id }.into());
+        }
+
+        Ok(type_id)
+    }
+
+    /// Ensures the type can hold a value.
+    fn value_type(&self, type_id: TypeId) -> Result<TypeId, Fail> {
+        match self.definition(self.type_exists(type_id)?) {
+            TypeDefinition::Void => Err(VerifyErrorKind::VoidValue {
+                instruction: self.instruction,
+            }
+            .into()),
+            _ => Ok(type_id),
+        }
+    }
+
+    fn type_of(&self, register: Register) -> Result<TypeId, Fail> {
+        self.types.get(&register).copied().ok_or(Fail::Unknown)
+    }
+
+    /// Ensures a register holds a type matching `predicate`, described by
+    /// `expected`.
+    fn expect<P: Fn(&TypeDefinition) -> bool>(
+        &self,
+        register: Register,
+        expected: &str,
+        predicate: P,
+    ) -> Result<TypeId, Fail> {
+        let type_id = self.type_of(register)?;
+
+        if !predicate(self.definition(type_id)) {
+            return Err(self.mismatch(register, expected.to_owned(), type_id));
+        }
+
+        Ok(type_id)
+    }
+
+    /// Ensures a register holds exactly the type `expected`.
+    fn expect_type(&self, register: Register, expected: TypeId) -> Result<TypeId, Fail> {
+        let type_id = self.type_of(register)?;
+
+        if type_id != expected {
+            return Err(self.mismatch(register, self.name(expected), type_id));
+        }
+
+        Ok(type_id)
+    }
+
+    fn mismatch(&self, register: Register, expected: String, found: TypeId) -> Fail {
+        VerifyErrorKind::OperandType {
+            instruction: self.instruction,
+            register,
+            expected,
+            found: self.name(found),
+        }
+        .into()
+    }
+
+    /// Ensures both registers hold the same type, which matches `predicate`.
+    fn expect_pair<P: Fn(&TypeDefinition) -> bool>(
+        &self,
+        a: Register,
+        b: Register,
+        expected: &str,
+        predicate: P,
+    ) -> Result<TypeId, Fail> {
+        let type_id = self.expect(a, expected, predicate)?;
+        self.expect_type(b, type_id)
+    }
+
+    /// Ensures a register is a pointer to a value, returning the type it
+    /// points to.
+    fn expect_pointee(&self, pointer: Register) -> Result<TypeId, Fail> {
+        let type_id = self.expect(
+            pointer,
+            "a pointer to a value",
+            |definition| match definition.pointee() {
+                Some(pointee) => !matches!(self.definition(pointee), TypeDefinition::Void),
+                None => false,
+            },
+        )?;
+
+        Ok(self.definition(type_id).pointee().unwrap())
+    }
+
+    /// Ensures a register is a pointer to a value that can be accessed
+    /// atomically, where `predicate` decides which values can be.
+    fn expect_atomic_pointee<P: Fn(&TypeDefinition) -> bool>(
+        &self,
+        pointer: Register,
+        expected: &str,
+        predicate: P,
+    ) -> Result<TypeId, Fail> {
+        let type_id = self.type_of(pointer)?;
+        let pointee = self.definition(type_id).pointee();
+
+        match pointee {
+            Some(pointee) if predicate(self.definition(pointee)) => Ok(pointee),
+            _ => Err(self.mismatch(pointer, expected.to_owned(), type_id)),
+        }
+    }
+
+    fn expect_field(&self, aggregate: TypeId, index: u32) -> Result<TypeId, Fail> {
+        self.definition(aggregate).field(index).ok_or_else(|| {
+            VerifyErrorKind::NoSuchField {
+                instruction: self.instruction,
+                index,
+                aggregate: self.name(aggregate),
+            }
+            .into()
+        })
+    }
+
+    fn conversion<P: Fn(&TypeDefinition, &TypeDefinition) -> bool>(
+        &self,
+        source: Register,
+        into: TypeId,
+        valid: P,
+    ) -> Result<Option<TypeId>, Fail> {
+        let from = self.type_of(source)?;
+        let into = self.type_exists(into)?;
+
+        if !valid(self.definition(from), self.definition(into)) {
+            return Err(VerifyErrorKind::InvalidConversion {
+                instruction: self.instruction,
+                from: self.name(from),
+                to: self.name(into),
+            }
+            .into());
+        }
+
+        Ok(Some(into))
+    }
+
+    /// Checks a call through a function pointer with the given signature.
+    /// Unlike direct calls, its arity and result can't be checked as it's
+    /// emitted, as the signature is only known from the pointer's type.
+    fn call_indirect(
+        &self,
+        callee: String,
+        signature: (TypeId, &[TypeId], bool),
+        result: Option<Register>,
+        arguments: &[Register],
+    ) -> Result<Option<TypeId>, Fail> {
+        let (return_type, parameters, variadic) = signature;
+
+        let arity_matches = if variadic {
+            arguments.len() >= parameters.len()
+        } else {
+            arguments.len() == parameters.len()
+        };
+
+        if !arity_matches {
+            return Err(VerifyErrorKind::CallArity {
+                callee,
+                expected: parameters.len(),
+                found: arguments.len(),
+                variadic,
+            }
+            .into());
+        }
+
+        for (argument, parameter) in arguments.iter().zip(parameters.iter()) {
+            self.expect_type(*argument, *parameter)?;
+        }
+
+        if let (Some(_), TypeDefinition::Void) = (result, self.definition(return_type)) {
+            return Err(VerifyErrorKind::VoidResult { callee }.into());
+        }
+
+        Ok(Some(return_type))
+    }
+
+    /// Checks a call of the intrinsic known as `name`. Every intrinsic that
+    /// produces a value produces the type of its first argument.
+    fn intrinsic(
+        &self,
+        name: &str,
+        result: Option<Register>,
+        arguments: &[Register],
+    ) -> Result<Option<TypeId>, Fail> {
+        let kind = IntrinsicKind::parse(name).ok_or_else(|| VerifyErrorKind::UnknownIntrinsic {
+            name: name.to_owned(),
+        })?;
+
+        if arguments.len() != kind.arity() {
+            return Err(VerifyErrorKind::IntrinsicArity {
+                name: name.to_owned(),
+                expected: kind.arity(),
+                found: arguments.len(),
+            }
+            .into());
+        }
+
+        match kind {
+            IntrinsicKind::IntegerUnary | IntrinsicKind::CountZeros => {
+                let type_id = self.expect(arguments[0], "an integer", is_integer)?;
+                let bits = self.definition(type_id).bits();
+
+                if name == "bswap" && bits % 16 != 0 {
+                    return Err(VerifyErrorKind::IntrinsicWidth {
+                        name: name.to_owned(),
+                        bits,
+                    }
+                    .into());
+                }
+
+                Ok(Some(type_id))
+            }
+            IntrinsicKind::FloatUnary | IntrinsicKind::FloatBinary => {
+                let type_id = self.expect(arguments[0], "a float", is_float)?;
+
+                for argument in arguments.iter().skip(1) {
+                    self.expect_type(*argument, type_id)?;
+                }
+
+                Ok(Some(type_id))
+            }
+            IntrinsicKind::MemoryTransfer | IntrinsicKind::MemorySet => {
+                self.expect(arguments[0], "a pointer", is_pointer)?;
+
+                match kind {
+                    IntrinsicKind::MemoryTransfer => {
+                        self.expect(arguments[1], "a pointer", is_pointer)?
+                    }
+                    _ => self.expect(arguments[1], "an 8 bit integer", |definition| {
+                        is_integer(definition) && definition.bits() == 8
+                    })?,
+                };
+
+                self.expect(arguments[2], "an integer", is_integer)?;
+
+                if result.is_some() {
+                    return Err(VerifyErrorKind::VoidResult {
+                        callee: name.to_owned(),
+                    }
+                    .into());
+                }
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// Checks the operands of an instruction, returning the type of the
+    /// register it defines, if any.
+    fn instruction_type(&self, inst: &Instruction) -> Result<Option<TypeId>, Fail> {
+        let meta = self.meta;
+        let method = self.method;
+
+        let type_id = match inst {
+            Instruction::Add { a, b, .. }
+            | Instruction::Sub { a, b, .. }
+            | Instruction::Mul { a, b, .. }
+            | Instruction::Div { a, b, .. }
+            | Instruction::Rem { a, b, .. }
+            | Instruction::And { a, b, .. }
+            | Instruction::Or { a, b, .. }
+            | Instruction::Xor { a, b, .. }
+            | Instruction::ShiftLeft { a, b, .. }
+            | Instruction::ShiftRight { a, b, .. }
+            | Instruction::AddChecked { a, b, .. }
+            | Instruction::SubChecked { a, b, .. }
+            | Instruction::MulChecked { a, b, .. } => {
+                self.expect_pair(*a, *b, "an integer", is_integer)?
+            }
+            Instruction::Compare { a, b, .. } => {
+                self.expect_pair(*a, *b, "an integer", is_integer)?;
+                meta.bool_type
+            }
+            Instruction::Neg { source, .. } | Instruction::Not { source, .. } => {
+                self.expect(*source, "an integer", is_integer)?
+            }
+            Instruction::FAdd { a, b, .. }
+            | Instruction::FSub { a, b, .. }
+            | Instruction::FMul { a, b, .. }
+            | Instruction::FDiv { a, b, .. }
+            | Instruction::FRem { a, b, .. } => self.expect_pair(*a, *b, "a float", is_float)?,
+            Instruction::FloatCompare { a, b, .. } => {
+                self.expect_pair(*a, *b, "a float", is_float)?;
+                meta.bool_type
+            }
+            Instruction::FNeg { source, .. } => self.expect(*source, "a float", is_float)?,
+            Instruction::IntToFloat {
+                source,
+                convert_into,
+                ..
+            } => {
+                return self.conversion(*source, *convert_into, |from, to| {
+                    is_integer(from) && is_float(to)
+                })
+            }
+            Instruction::FloatToInt {
+                source,
+                convert_into,
+                ..
+            } => {
+                return self.conversion(*source, *convert_into, |from, to| {
+                    is_float(from) && is_integer(to)
+                })
+            }
+            Instruction::Truncate {
+                source,
+                truncate_into,
+                ..
+            } => {
+                return self.conversion(*source, *truncate_into, |from, to| {
+                    is_integer(from) && is_integer(to) && to.bits() < from.bits()
+                })
+            }
+            Instruction::SignExtend {
+                source,
+                extend_into,
+                ..
+            }
+            | Instruction::ZeroExtend {
+                source,
+                extend_into,
+                ..
+            } => {
+                return self.conversion(*source, *extend_into, |from, to| {
+                    is_integer(from) && is_integer(to) && to.bits() > from.bits()
+                })
+            }
+            Instruction::Cast {
+                source, cast_into, ..
+            } => {
+                return self.conversion(*source, *cast_into, |from, to| {
+                    (is_sized(from) && is_sized(to)) || (is_pointer(from) && is_pointer(to))
+                })
+            }
+            Instruction::Bitcast {
+                source, cast_into, ..
+            } => {
+                return self.conversion(*source, *cast_into, |from, to| {
+                    (is_pointer(from) && is_pointer(to))
+                        || (is_sized(from) && is_sized(to) && to.bits() == from.bits())
+                })
+            }
+            Instruction::PointerToInt {
+                source,
+                convert_into,
+                ..
+            } => {
+                return self.conversion(*source, *convert_into, |from, to| {
+                    is_pointer(from) && is_integer(to)
+                })
+            }
+            Instruction::IntToPointer {
+                source,
+                convert_into,
+                ..
+            } => {
+                return self.conversion(*source, *convert_into, |from, to| {
+                    is_integer(from) && is_pointer(to)
+                })
+            }
+            Instruction::LoadParameter {
+                parameter_number, ..
+            } => match method.parameters.get(*parameter_number as usize) {
+                Some(parameter) if *parameter_number >= 0 => *parameter,
+                _ => {
+                    return Err(VerifyErrorKind::ParameterOutOfBounds {
+                        parameter: *parameter_number,
+                        parameters: method.parameters.len(),
+                    }
+                    .into())
+                }
+            },
+            Instruction::LoadConstant {
+                type_id, constant, ..
+            } => {
+                let valid = match self.definition(self.type_exists(*type_id)?) {
+                    TypeDefinition::Integer { signed, bits } => {
+                        constant.to_words(*signed, *bits).map(|_| ())
+                    }
+                    TypeDefinition::Float { .. } => Ok(()),
+                    _ => Err(format!(
+                        "constants must be numbers, not {}",
+                        self.name(*type_id)
+                    )),
+                };
+
+                if let Err(message) = valid {
+                    return Err(VerifyErrorKind::InvalidConstant { message }.into());
+                }
+
+                *type_id
+            }
+            Instruction::LoadZero { type_id, .. } => self.value_type(*type_id)?,
+            Instruction::Call {
+                method_id,
+                parameters,
+                ..
+            } => {
+                let callee = match meta.methods.get(*method_id as usize) {
+                    Some(callee) if *method_id >= 0 => callee,
+                    _ => {
+                        return Err(VerifyErrorKind::NoSuchMethod {
+                            method_id: *method_id,
+                        }
+                        .into())
+                    }
+                };
+
+                // the arity and result were checked when the call was emitted,
+                // but the arguments may not have existed yet
+                for (argument, parameter) in parameters.iter().zip(callee.parameters.iter()) {
+                    self.expect_type(*argument, *parameter)?;
+                }
+
+                return Ok(Some(callee.return_type));
+            }
+            Instruction::CallIndirect {
+                result,
+                pointer,
+                parameters,
+            } => {
+                let type_id = self.expect(*pointer, "a function pointer", |definition| {
+                    matches!(definition, TypeDefinition::FunctionPointer { .. })
+                })?;
+
+                return match self.definition(type_id) {
+                    TypeDefinition::FunctionPointer {
+                        return_type,
+                        parameters: expected,
+                        variadic,
+                    } => self.call_indirect(
+                        format!("register {}", pointer),
+                        (*return_type, expected.as_slice(), *variadic),
+                        *result,
+                        parameters,
+                    ),
+                    _ => unreachable!(),
+                };
+            }
+            Instruction::Return { result } => {
+                let return_type = method.return_type;
+
+                let found = match (result, self.definition(return_type)) {
+                    (None, TypeDefinition::Void) => return Ok(None),
+                    (None, _) => "nothing".to_owned(),
+                    (Some(result), _) => {
+                        let found = self.type_of(*result)?;
+
+                        if found == return_type {
+                            return Ok(None);
+                        }
+
+                        self.name(found)
+                    }
+                };
+
+                return Err(VerifyErrorKind::ReturnType {
+                    expected: self.name(return_type),
+                    found,
+                }
+                .into());
+            }
+            Instruction::ConditionalBranch { condition, .. } => {
+                self.expect_type(*condition, meta.bool_type)?;
+                return Ok(None);
+            }
+            Instruction::Switch {
+                condition, cases, ..
+            } => {
+                let type_id = self.expect(*condition, "an integer", is_integer)?;
+                let definition = self.definition(type_id);
+
+                // cases are compared by their bits, as `0x0` and `-0` are the
+                // same case
+                let mut seen = Vec::with_capacity(cases.len());
+
+                for (constant, _) in cases.iter() {
+                    let words = constant
+                        .to_words(definition.is_signed(), definition.bits())
+                        .map_err(|message| VerifyErrorKind::InvalidConstant { message })?;
+
+                    if seen.contains(&words) {
+                        return Err(VerifyErrorKind::DuplicateSwitchCase {
+                            case: constant.to_string(),
+                        }
+                        .into());
+                    }
+
+                    seen.push(words);
+                }
+
+                return Ok(None);
+            }
+            Instruction::Select {
+                condition, a, b, ..
+            } => {
+                self.expect_type(*condition, meta.bool_type)?;
+                self.expect_pair(*a, *b, "a value", |_| true)?
+            }
+            Instruction::Phi { type_id, .. } => self.value_type(*type_id)?,
+            Instruction::LoadLocal { local_id, .. } => method.locals[*local_id as usize],
+            Instruction::StoreLocal { local_id, source } => {
+                self.expect_type(*source, method.locals[*local_id as usize])?;
+                return Ok(None);
+            }
+            Instruction::AddressOf { local_id, .. } => {
+                let pointee = method.locals[*local_id as usize];
+                meta.find_type(&TypeDefinition::Pointer { pointee })
+                    .expect("expected pointer type to be created alongside the local")
+            }
+            Instruction::LoadPointer { pointer, .. } => self.expect_pointee(*pointer)?,
+            Instruction::StorePointer { pointer, source } => {
+                let pointee = self.expect_pointee(*pointer)?;
+                self.expect_type(*source, pointee)?;
+                return Ok(None);
+            }
+            Instruction::AtomicLoad { pointer, .. } => self.expect_atomic_pointee(
+                *pointer,
+                "a pointer to a power of two byte integer, a float or a pointer",
+                is_atomic_value,
+            )?,
+            Instruction::AtomicStore {
+                pointer, source, ..
+            } => {
+                let pointee = self.expect_atomic_pointee(
+                    *pointer,
+                    "a pointer to a power of two byte integer, a float or a pointer",
+                    is_atomic_value,
+                )?;
+                self.expect_type(*source, pointee)?;
+                return Ok(None);
+            }
+            Instruction::Offset { pointer, index, .. } => {
+                self.expect(*index, "an integer", is_integer)?;
+                self.expect(*pointer, "a pointer", is_pointer)?
+            }
+            Instruction::ExtractField {
+                aggregate, index, ..
+            } => self.expect_field(self.type_of(*aggregate)?, *index)?,
+            Instruction::InsertField {
+                aggregate,
+                index,
+                source,
+                ..
+            } => {
+                let type_id = self.type_of(*aggregate)?;
+                let field = self.expect_field(type_id, *index)?;
+                self.expect_type(*source, field)?;
+                type_id
+            }
+            Instruction::FieldPointer { pointer, index, .. } => {
+                let pointee = self.expect_pointee(*pointer)?;
+                let field = self.expect_field(pointee, *index)?;
+
+                meta.find_type(&TypeDefinition::Pointer { pointee: field })
+                    .expect("expected pointer type to be created alongside the aggregate")
+            }
+            Instruction::GlobalAddress { global_id, .. } => {
+                meta.globals[*global_id as usize].pointer_type
+            }
+            Instruction::MethodAddress { method_id, .. } => {
+                let callee = match meta.methods.get(*method_id as usize) {
+                    Some(callee) if *method_id >= 0 => callee,
+                    _ => {
+                        return Err(VerifyErrorKind::NoSuchMethod {
+                            method_id: *method_id,
+                        }
+                        .into())
+                    }
+                };
+
+                if callee.options.calling_convention != CallingConvention::C {
+                    return Err(VerifyErrorKind::AddressOfNonCMethod {
+                        method: callee.name.clone(),
+                    }
+                    .into());
+                }
+
+                meta.find_type(&callee.signature())
+                    .expect("expected function pointer type to be created alongside the method")
+            }
+            Instruction::Intrinsic {
+                result,
+                name,
+                arguments,
+            } => return self.intrinsic(name, *result, arguments),
+            // only exchanging can be done on floats, as arithmetic on their
+            // bits would be meaningless
+            Instruction::AtomicRmw {
+                operation: AtomicOperation::Exchange,
+                pointer,
+                source,
+                ..
+            } => {
+                let pointee = self.expect_atomic_pointee(
+                    *pointer,
+                    "a pointer to a power of two byte integer or a float",
+                    |definition| is_atomic_integer(definition) || is_float(definition),
+                )?;
+                self.expect_type(*source, pointee)?
+            }
+            Instruction::AtomicRmw {
+                pointer, source, ..
+            } => {
+                let pointee = self.expect_atomic_pointee(
+                    *pointer,
+                    "a pointer to a power of two byte integer",
+                    is_atomic_integer,
+                )?;
+                self.expect_type(*source, pointee)?
+            }
+            Instruction::CompareExchange {
+                pointer,
+                expected,
+                replacement,
+                ..
+            } => {
+                let pointee = self.expect_atomic_pointee(
+                    *pointer,
+                    "a pointer to a power of two byte integer or a pointer",
+                    |definition| is_atomic_integer(definition) || is_any_pointer(definition),
+                )?;
+                self.expect_type(*expected, pointee)?;
+                self.expect_type(*replacement, pointee)?
+            }
+            Instruction::Branch { .. }
+            | Instruction::Unreachable
+            | Instruction::Trap
+            | Instruction::Fence { .. } => return Ok(None),
+        };
+
+        Ok(Some(type_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::js_boundary::{BlockDefinition, MethodOptions};
+
+    /// A method whose blocks only hold the given terminators.
+    fn method(terminators: Vec<Instruction>) -> MethodDefinition {
+        MethodDefinition {
+            name: "test".to_owned(),
+            return_type: 0,
+            parameters: vec![],
+            locals: vec![],
+            blocks: terminators
+                .into_iter()
+                .enumerate()
+                .map(|(block_id, terminator)| BlockDefinition {
+                    name: format!("b{}", block_id),
+                    instructions: vec![terminator],
+                })
+                .collect(),
+            external: false,
+            variadic: false,
+            options: MethodOptions::default(),
+        }
+    }
+
+    fn br(target: BlockId) -> Instruction {
+        Instruction::Branch { target }
+    }
+
+    fn cond_br(then_block: BlockId, else_block: BlockId) -> Instruction {
+        Instruction::ConditionalBranch {
+            condition: 0,
+            then_block,
+            else_block,
+        }
+    }
+
+    fn ret() -> Instruction {
+        Instruction::Return { result: None }
+    }
+
+    #[test]
+    fn diamond() {
+        let control_flow = ControlFlow::new(&method(vec![cond_br(1, 2), br(3), br(3), ret()]));
+
+        assert_eq!(control_flow.predecessors(0), &[] as &[BlockId]);
+        assert_eq!(control_flow.predecessors(3), &[1, 2]);
+
+        for block in 0..4 {
+            assert!(control_flow.dominates(0, block));
+            assert!(control_flow.dominates(block, block));
+        }
+
+        assert!(!control_flow.dominates(1, 3));
+        assert!(!control_flow.dominates(2, 3));
+        assert!(!control_flow.dominates(1, 2));
+        assert!(!control_flow.dominates(3, 0));
+
+        let order = control_flow.emission_order();
+        assert_eq!(order.first(), Some(&0));
+        assert_eq!(order.last(), Some(&3));
+    }
+
+    #[test]
+    fn loops() {
+        // b0 -> b1 -> b2 -> b1, and b1 -> b3
+        let control_flow = ControlFlow::new(&method(vec![br(1), cond_br(2, 3), br(1), ret()]));
+
+        assert_eq!(control_flow.predecessors(1), &[0, 2]);
+        assert_eq!(control_flow.predecessors(3), &[1]);
+
+        assert!(control_flow.dominates(1, 2));
+        assert!(control_flow.dominates(1, 3));
+        assert!(!control_flow.dominates(2, 1));
+        assert!(!control_flow.dominates(2, 3));
+
+        assert_eq!(&control_flow.emission_order()[..2], &[0, 1]);
+    }
+
+    #[test]
+    fn unreachable_blocks() {
+        // b1 is never branched to, but still branches into the rest
+        let control_flow = ControlFlow::new(&method(vec![br(2), br(2), ret()]));
+
+        assert_eq!(control_flow.predecessors(2), &[0, 1]);
+
+        // only the reachable predecessor decides what dominates b2
+        assert!(control_flow.dominates(0, 2));
+        assert!(!control_flow.dominates(1, 2));
+
+        assert!(control_flow.dominates(1, 1));
+        assert!(!control_flow.dominates(0, 1));
+
+        assert_eq!(control_flow.emission_order(), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn repeated_edges() {
+        let control_flow = ControlFlow::new(&method(vec![cond_br(1, 1), ret()]));
+
+        assert_eq!(control_flow.predecessors(1), &[0, 0]);
+        assert!(control_flow.dominates(0, 1));
+        assert_eq!(control_flow.emission_order(), vec![0, 1]);
+    }
+}
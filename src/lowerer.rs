@@ -6,7 +6,10 @@ use std::{
 };
 use tree_sitter::{Tree, TreeCursor};
 
-use crate::ir::IrBuilder;
+use crate::{
+    diagnostics::{DiagnosticsHandle, Level},
+    ir::{verify::VerifyError, IrBuilder},
+};
 
 pub struct Lowerer {
     context: Context,
@@ -17,6 +20,7 @@ impl Lowerer {
     pub fn new<F: FnOnce(&mut Context) -> IrBuilder>(
         source: &str,
         tree: Tree,
+        diagnostics: DiagnosticsHandle,
         ir_builder_factory: F,
     ) -> Self {
         let mut context = Context::builder()
@@ -47,6 +51,25 @@ impl Lowerer {
             })
             .expect("expected to add callback `toValue`");
 
+        // the primer wraps this as `context.error`, `context.warning` and
+        // `context.note`
+        context
+            .add_callback(
+                "__compiler_diagnostic",
+                move |level: String, range: f64, message: String| {
+                    let level = match Level::parse(level.as_str()) {
+                        Some(level) => level,
+                        None => return Err(format!("unknown diagnostic level `{}`", level)),
+                    };
+
+                    let mut diagnostics = diagnostics.lock().unwrap();
+                    diagnostics.report(level, f64_to_range(range), message.as_str());
+
+                    Ok(JsValue::Undefined)
+                },
+            )
+            .expect("expected to add callback `__compiler_diagnostic`");
+
         let ir_builder = ir_builder_factory(&mut context);
 
         Lowerer {
@@ -56,19 +79,41 @@ impl Lowerer {
     }
 
     pub fn exec(&self, lowerer_src: &str) -> Result<JsValue, ExecutionError> {
-        self.context.eval(lowerer_src)
+        run_lowerer(&self.context, lowerer_src)
+    }
+
+    pub fn verify(&self) -> Vec<VerifyError> {
+        self.ir_builder.verify()
     }
 
     pub fn make_llvm<'ctx>(
         &self,
         context: &'ctx inkwell::context::Context,
-    ) -> inkwell::module::Module<'ctx> {
+    ) -> Result<inkwell::module::Module<'ctx>, String> {
         let mut module = context.create_module("tinkerlang_module");
-        self.ir_builder.hydrate(&context, &mut module);
-        module
+        self.ir_builder.hydrate(&context, &mut module)?;
+        Ok(module)
     }
 }
 
+/// Runs the lowerer through an indirect `eval`, so that its stack frames are
+/// named `<input>` rather than sharing a name with the primer. quick-js only
+/// keeps what an uncaught error stringifies to, so the stack is added to the
+/// message before it leaves the context.
+fn run_lowerer(context: &Context, lowerer_src: &str) -> Result<JsValue, ExecutionError> {
+    context.set_global("__lowerer_source", lowerer_src.to_owned())?;
+
+    context.eval(
+        r#"
+        try {
+            (0, eval)(__lowerer_source);
+        } catch (error) {
+            throw error instanceof Error ? `${error}\n${error.stack}` : String(error);
+        }
+        "#,
+    )
+}
+
 struct DisplayJsValue<'a>(&'a JsValue);
 
 impl<'a> fmt::Display for DisplayJsValue<'a> {
@@ -116,6 +161,10 @@ fn alloc_to_js_heap(tree: tree_sitter::Tree, input: &str) -> JsValue {
             range_to_js(node.start_byte()..node.end_byte()),
         );
 
+        map.insert("isError".to_owned(), JsValue::Bool(node.is_error()));
+        map.insert("isMissing".to_owned(), JsValue::Bool(node.is_missing()));
+        map.insert("hasError".to_owned(), JsValue::Bool(node.has_error()));
+
         map.insert("children".to_owned(), JsValue::Array(children));
 
         map
@@ -163,3 +212,24 @@ fn f64_to_range(float: f64) -> Range<usize> {
 
     start as usize..end as usize
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_have_lowerer_stack_trace() {
+        let mut context = Context::new().expect("expected to create a context");
+        let _ir_builder = IrBuilder::new(&mut context);
+
+        let lowerer = "const u = 1;\ncontext.method(\"broken\", 12345, []);\n";
+
+        match run_lowerer(&context, lowerer) {
+            Err(ExecutionError::Exception(JsValue::String(message))) => {
+                assert!(message.contains("no type with id 12345"), "{}", message);
+                assert!(message.contains("<input>:2"), "{}", message);
+            }
+            result => panic!("expected the lowerer to throw, got {:?}", result),
+        }
+    }
+}
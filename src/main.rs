@@ -1,16 +1,23 @@
 extern crate inkwell;
 
-use std::{marker::PhantomData, path::PathBuf};
+use std::{
+    marker::PhantomData,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
+use diagnostics::{Diagnostics, Level};
 use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target};
 use inkwell::OptimizationLevel;
 use inkwell::{context::Context, targets::TargetMachine};
 use ir::IrBuilder;
 use lld_sys::{llvm_ArrayRef_size_type, llvm_raw_ostream};
 use lowerer::Lowerer;
+use quick_js::{ExecutionError, JsValue};
 use structopt::StructOpt;
 use tree_sitter::{Language, Parser};
 
+pub(crate) mod diagnostics;
 pub(crate) mod ir;
 pub(crate) mod lowerer;
 
@@ -35,6 +42,12 @@ struct TinkerlangOptions {
     /// IR, then pushed through LLVM's pipeline and into an output binary.
     #[structopt(short, long)]
     input: String,
+
+    /// Lower the input even if it has syntax errors, reporting them as
+    /// warnings. The lowerer sees them as nodes with `isError` or `isMissing`
+    /// set.
+    #[structopt(long)]
+    allow_syntax_errors: bool,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -43,30 +56,91 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         parser: "javascript".to_owned(),
         input: "../ex.js".to_owned(),
         lowerer: "../l.js".to_owned(),
+        allow_syntax_errors: false,
     };
     #[cfg(not(debug_assertions))]
     let options = TinkerlangOptions::from_args();
 
     let language = load_language(options.parser.as_str());
-    let input = std::fs::read_to_string(options.input).expect("expected to read input into string");
+    let input =
+        std::fs::read_to_string(&options.input).expect("expected to read input into string");
+    let diagnostics = Arc::new(Mutex::new(Diagnostics::new(
+        options.input.as_str(),
+        input.as_str(),
+    )));
 
     let mut parser = Parser::new();
     parser
         .set_language(language)
         .expect("expected to set language");
 
-    let tree = parser.parse(input.as_str(), None).unwrap();
-    let lowerer = Lowerer::new(input.as_str(), tree, |ctx| IrBuilder::new(ctx));
+    let tree = parser
+        .parse(input.as_str(), None)
+        .expect("expected parser to produce a tree");
+
+    let level = if options.allow_syntax_errors {
+        Level::Warning
+    } else {
+        Level::Error
+    };
+
+    let syntax_errors = diagnostics
+        .lock()
+        .unwrap()
+        .report_syntax_errors(tree.root_node(), level);
+
+    if syntax_errors > 0 && !options.allow_syntax_errors {
+        eprintln!("aborting due to {} syntax error(s)", syntax_errors);
+        std::process::exit(1);
+    }
+
+    let lowerer = Lowerer::new(input.as_str(), tree, diagnostics.clone(), |ctx| {
+        IrBuilder::new(ctx)
+    });
 
     let lowerer_src =
         std::fs::read_to_string(options.lowerer).expect("expected to read lowerer into string");
 
-    lowerer
-        .exec(lowerer_src.as_str())
-        .expect("to run lowerer successfully");
+    if let Err(error) = lowerer.exec(lowerer_src.as_str()) {
+        // errors thrown by the lowerer, including bad IR, come with the JS
+        // stack trace of where they were thrown, where the lowerer's frames
+        // are named `<input>`
+        match error {
+            ExecutionError::Exception(JsValue::String(message)) => {
+                eprintln!("error: {}", message)
+            }
+            error => eprintln!("error: {}", error),
+        }
+
+        eprintln!("aborting due to an error in the lowerer");
+        std::process::exit(1);
+    }
+
+    let error_count = diagnostics.lock().unwrap().error_count();
+    if error_count > 0 {
+        eprintln!("aborting due to {} previous error(s)", error_count);
+        std::process::exit(1);
+    }
+
+    let errors = lowerer.verify();
+    if !errors.is_empty() {
+        for error in errors.iter() {
+            eprintln!("error: {}", error);
+        }
+
+        eprintln!("aborting due to {} IR error(s)", errors.len());
+        std::process::exit(1);
+    }
 
     let context = Context::create();
-    let llvm_module = lowerer.make_llvm(&context);
+    let llvm_module = match lowerer.make_llvm(&context) {
+        Ok(llvm_module) => llvm_module,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            eprintln!("aborting due to invalid LLVM IR");
+            std::process::exit(1);
+        }
+    };
 
     let ir = llvm_module.print_to_string().to_string();
     println!("LLVM IR:\n{}", ir);